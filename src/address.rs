@@ -0,0 +1,39 @@
+//! Dash address decoding.
+
+/// Error type to indicate an address that cannot be inserted into a Bloom filter.
+#[derive(Debug, thiserror::Error)]
+pub enum InvalidAddress {
+    /// Address is not valid base58check
+    #[error("invalid base58check encoding: {0}")]
+    Base58(#[from] bs58::decode::Error),
+    /// Decoded payload is not a version byte followed by a 20 bytes hash
+    #[error("invalid address payload length {0}")]
+    InvalidLength(usize),
+    /// Version byte is not one of Dash P2PKH/P2SH mainnet/testnet prefixes
+    #[error("unknown address version {0}")]
+    UnknownVersion(u8),
+}
+
+const PUBKEY_ADDRESS_MAINNET: u8 = 76;
+const SCRIPT_ADDRESS_MAINNET: u8 = 16;
+const PUBKEY_ADDRESS_TESTNET: u8 = 140;
+const SCRIPT_ADDRESS_TESTNET: u8 = 19;
+
+/// Decode a P2PKH or P2SH Dash address into the underlying hash160.
+pub(crate) fn decode_hash160(address: &str) -> Result<[u8; 20], InvalidAddress> {
+    let payload = bs58::decode(address).with_check(None).into_vec()?;
+    let [version, hash @ ..] = payload.as_slice() else {
+        return Err(InvalidAddress::InvalidLength(0));
+    };
+    let hash: [u8; 20] = hash
+        .try_into()
+        .map_err(|_| InvalidAddress::InvalidLength(payload.len()))?;
+
+    match *version {
+        PUBKEY_ADDRESS_MAINNET
+        | SCRIPT_ADDRESS_MAINNET
+        | PUBKEY_ADDRESS_TESTNET
+        | SCRIPT_ADDRESS_TESTNET => Ok(hash),
+        version => Err(InvalidAddress::UnknownVersion(version)),
+    }
+}
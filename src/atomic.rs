@@ -0,0 +1,103 @@
+//! Bloom filter shared between threads without locking.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::{
+    filter::FilterBits, hasher::Hasher, BadFilterParameters, BloomFilter, BloomFilterBuilder,
+    BloomFlags,
+};
+
+/// Bloom filter with bits stored in atomic words, so any number of threads can insert
+/// and query through a shared reference without locks.
+///
+/// Bits are only ever set, so relaxed atomics suffice: an element is visible to every
+/// query that starts after its insertion returned, and a concurrent query may miss an
+/// element being inserted but never sees a false negative for a completed insertion.
+///
+/// Sizing and hashing are the same as of [BloomFilter], so it can be exported for use
+/// on the wire with [Self::to_bloom_filter].
+#[derive(Debug)]
+pub struct AtomicBloomFilter {
+    words: Vec<AtomicU64>,
+    n_tweak: u32,
+    hasher: Hasher,
+}
+
+impl AtomicBloomFilter {
+    /// Create new atomic Bloom filter sized like at [BloomFilterBuilder::new_n_tweak]
+    pub fn new(
+        n_elements: u32,
+        false_positives_rate: f64,
+        n_tweak: u32,
+    ) -> Result<Self, BadFilterParameters> {
+        BloomFilterBuilder::new_n_tweak(n_elements, false_positives_rate, n_tweak)
+            .map(|builder| Self::from(builder.build()))
+    }
+
+    /// Add element to the filter, returns `false` if it was already covered by the
+    /// filter
+    pub fn insert(&self, element: &[u8]) -> bool {
+        let mut changed = false;
+        for i in self.hasher.hash_indexes(element) {
+            let mask = 1 << (i % 64);
+            changed |= self.words[i / 64].fetch_or(mask, Ordering::Relaxed) & mask == 0;
+        }
+        changed
+    }
+
+    /// Check if the filter possibly contains the element
+    pub fn probably_contains(&self, element: &[u8]) -> bool {
+        self.hasher
+            .hash_indexes(element)
+            .all(|i| self.words[i / 64].load(Ordering::Relaxed) & (1 << (i % 64)) != 0)
+    }
+
+    /// Get filter size in bytes
+    pub fn size_bytes(&self) -> usize {
+        self.hasher.filter_bits_len / 8
+    }
+
+    /// Remove all elements.  Elements inserted concurrently may or may not be kept.
+    pub fn clear(&self) {
+        for word in &self.words {
+            word.store(0, Ordering::Relaxed);
+        }
+    }
+
+    /// Export a snapshot of the filter as a BIP-37 Bloom filter matching the same
+    /// elements
+    pub fn to_bloom_filter(&self, n_flags: BloomFlags) -> BloomFilter {
+        let words = self
+            .words
+            .iter()
+            .map(|word| word.load(Ordering::Relaxed))
+            .collect();
+        let mut filter_bits = FilterBits::from_vec(words);
+        filter_bits.truncate(self.hasher.filter_bits_len);
+
+        BloomFilter {
+            filter_bits,
+            n_tweak: self.n_tweak,
+            n_flags,
+            hasher: self.hasher.clone(),
+            pending_filteradd: None,
+            dirty: true,
+        }
+    }
+}
+
+/// Shares an existing filter between threads, e.g. one loaded by a peer.
+impl From<BloomFilter> for AtomicBloomFilter {
+    fn from(mut filter: BloomFilter) -> Self {
+        AtomicBloomFilter {
+            words: filter
+                .filter_bits
+                .as_raw_slice()
+                .iter()
+                .map(|&word| AtomicU64::new(word))
+                .collect(),
+            n_tweak: filter.n_tweak,
+            hasher: std::mem::take(&mut filter.hasher),
+        }
+    }
+}
@@ -0,0 +1,399 @@
+//! Command line tool for building and inspecting BIP-37 Bloom filters.
+
+use std::{
+    fs,
+    io::{self, Read},
+    process::ExitCode,
+};
+
+use dashcore_bloom_filter::{
+    BloomFilter, BloomFilterBuilder, BloomFlags, MatchReason, RelevanceScanner, Transaction,
+};
+
+const USAGE: &str = "\
+Usage:
+  bip37-filter build [--fp-rate RATE] [--tweak TWEAK] [--flags FLAGS] [FILE]
+  bip37-filter match FILTER TRANSACTION
+
+Commands:
+  build    Build a filter from elements, one per line of FILE or stdin, given
+           as hex or as base58 Dash addresses, and print filterload payload hex
+  match    Check if a raw transaction given as hex matches a filter given as
+           filterload payload hex, and print why
+
+Options:
+  --fp-rate RATE    False positives rate, defaults to 0.0001
+  --tweak TWEAK     nTweak, defaults to 0
+  --flags FLAGS     none, all or p2pubkey-only, defaults to none
+";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let result = match args.split_first() {
+        Some((command, args)) if command == "build" => build(args, io::stdin()),
+        Some((command, args)) if command == "match" => match_transaction(args),
+        _ => Err(USAGE.to_owned()),
+    };
+
+    match result {
+        Ok(output) => {
+            println!("{output}");
+            ExitCode::SUCCESS
+        }
+        Err(error) => {
+            eprintln!("{error}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Build a filter from elements read from the file given in `args` or from `stdin`
+fn build(args: &[String], stdin: impl Read) -> Result<String, String> {
+    let mut false_positives_rate = 0.0001;
+    let mut n_tweak = 0;
+    let mut n_flags = BloomFlags::UpdateNone;
+    let mut path = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("missing value for {arg}"))
+        };
+        match arg.as_str() {
+            "--fp-rate" => {
+                false_positives_rate = value()?
+                    .parse()
+                    .map_err(|_| format!("invalid false positives rate for {arg}"))?
+            }
+            "--tweak" => {
+                n_tweak = value()?
+                    .parse()
+                    .map_err(|_| format!("invalid nTweak for {arg}"))?
+            }
+            "--flags" => n_flags = parse_flags(value()?)?,
+            _ if arg.starts_with("--") => return Err(format!("unknown option {arg}\n\n{USAGE}")),
+            _ => path = Some(arg),
+        }
+    }
+
+    let input = read_input(path.map(String::as_str), stdin)?;
+    let elements: Vec<&str> = input
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect();
+
+    let n_elements = u32::try_from(elements.len().max(1)).unwrap_or(u32::MAX);
+    let mut builder = BloomFilterBuilder::new_n_tweak(n_elements, false_positives_rate, n_tweak)
+        .map_err(|err| err.to_string())?;
+    for element in elements {
+        builder = add_element(builder, element)?;
+    }
+
+    Ok(builder.build_with_n_flags(n_flags).to_rpc_hex())
+}
+
+fn match_transaction(args: &[String]) -> Result<String, String> {
+    let [filter, transaction] = args else {
+        return Err(USAGE.to_owned());
+    };
+
+    let filter =
+        BloomFilter::from_rpc_hex(filter).map_err(|err| format!("invalid filter: {err}"))?;
+    let transaction = decode_hex(transaction)
+        .ok_or_else(|| "transaction is not hex".to_owned())
+        .and_then(|bytes| {
+            Transaction::from_bytes(&bytes).map_err(|err| format!("invalid transaction: {err}"))
+        })?;
+
+    let Some(matched) = RelevanceScanner::new(filter).scan(&transaction) else {
+        return Ok(format!(
+            "{} does not match",
+            display_txid(&transaction.txid)
+        ));
+    };
+
+    let mut output = format!("{} matches", display_txid(&matched.txid));
+    for reason in matched.reasons {
+        let line = match reason {
+            MatchReason::Txid => "txid".to_owned(),
+            MatchReason::OutputElement { vout, element } => {
+                format!("output {vout} element {}", encode_hex(&element))
+            }
+            MatchReason::SpentOutpoint(outpoint) => {
+                format!(
+                    "spent outpoint {}:{}",
+                    display_txid(&outpoint.txid),
+                    outpoint.vout
+                )
+            }
+            MatchReason::InputElement { index, element } => {
+                format!("input {index} element {}", encode_hex(&element))
+            }
+        };
+        output.push_str("\n  ");
+        output.push_str(&line);
+    }
+    Ok(output)
+}
+
+fn parse_flags(flags: &str) -> Result<BloomFlags, String> {
+    match flags {
+        "none" | "0" => Ok(BloomFlags::UpdateNone),
+        "all" | "1" => Ok(BloomFlags::UpdateAll),
+        "p2pubkey-only" | "2" => Ok(BloomFlags::UpdateP2PubkeyOnly),
+        _ => Err(format!("unknown flags {flags}")),
+    }
+}
+
+fn read_input(path: Option<&str>, mut stdin: impl Read) -> Result<String, String> {
+    match path {
+        None | Some("-") => {
+            let mut input = String::new();
+            stdin
+                .read_to_string(&mut input)
+                .map_err(|err| format!("cannot read stdin: {err}"))?;
+            Ok(input)
+        }
+        Some(path) => fs::read_to_string(path).map_err(|err| format!("cannot read {path}: {err}")),
+    }
+}
+
+/// Add an element given as hex, or as an address whose hash160 is added
+fn add_element(builder: BloomFilterBuilder, element: &str) -> Result<BloomFilterBuilder, String> {
+    match decode_hex(element) {
+        Some(bytes) => Ok(builder.add_element(&bytes)),
+        None => builder
+            .add_address(element)
+            .map_err(|err| format!("element {element} is neither hex nor an address: {err}")),
+    }
+}
+
+fn decode_hex(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) || !s.bytes().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).ok())
+        .collect()
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Format txid in the reversed byte order used by RPC and block explorers
+fn display_txid(txid: &[u8; 32]) -> String {
+    let mut reversed = *txid;
+    reversed.reverse();
+    encode_hex(&reversed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    fn address(hash: &[u8; 20]) -> String {
+        bs58::encode([&[76][..], hash].concat())
+            .with_check()
+            .into_string()
+    }
+
+    #[test]
+    fn build_from_stdin() {
+        let address = address(&[0x42; 20]);
+        let input = format!("deadbeef\n\n  {address}  \n00\n");
+
+        let output = build(
+            &args(&["--fp-rate", "0.01", "--tweak", "5", "--flags", "all"]),
+            input.as_bytes(),
+        )
+        .expect("input is valid");
+
+        let expected = BloomFilterBuilder::new_n_tweak(3, 0.01, 5)
+            .expect("parameters are correct")
+            .add_element(&[0xDE, 0xAD, 0xBE, 0xEF])
+            .add_address(&address)
+            .expect("address is valid")
+            .add_element(&[0x00])
+            .build_with_n_flags(BloomFlags::UpdateAll);
+        assert_eq!(output, expected.to_rpc_hex());
+        assert_eq!(
+            build(
+                &args(&["-", "--fp-rate", "0.01", "--tweak", "5", "--flags", "1"]),
+                input.as_bytes()
+            ),
+            Ok(output)
+        );
+    }
+
+    #[test]
+    fn build_defaults() {
+        let output = build(&[], "".as_bytes()).expect("input is valid");
+        let expected = BloomFilterBuilder::new_n_tweak(1, 0.0001, 0)
+            .expect("parameters are correct")
+            .build_with_n_flags(BloomFlags::UpdateNone);
+        assert_eq!(output, expected.to_rpc_hex());
+    }
+
+    #[test]
+    fn build_from_file() {
+        let path = std::env::temp_dir().join(format!("bip37-filter-{}.txt", std::process::id()));
+        fs::write(&path, "deadbeef\n00\n").expect("temporary file is writable");
+        let path = path.to_str().expect("path is UTF-8").to_owned();
+
+        let from_file = build(&args(&["--flags", "p2pubkey-only", &path]), "ff".as_bytes());
+        fs::remove_file(&path).expect("temporary file exists");
+
+        let from_stdin = build(&args(&["--flags", "2"]), "deadbeef\n00\n".as_bytes());
+        assert_eq!(from_file, from_stdin);
+        let filter = BloomFilter::from_rpc_hex(&from_file.expect("input is valid"))
+            .expect("output is valid");
+        assert_eq!(filter.flags(), BloomFlags::UpdateP2PubkeyOnly);
+        assert!(filter.probably_contains(&[0xDE, 0xAD, 0xBE, 0xEF]));
+        assert!(!filter.probably_contains(&[0xFF]));
+
+        assert!(build(&args(&[&path]), "".as_bytes())
+            .unwrap_err()
+            .starts_with("cannot read"));
+    }
+
+    #[test]
+    fn build_errors() {
+        let build = |arguments: &[&str], input: &str| build(&args(arguments), input.as_bytes());
+
+        assert_eq!(
+            build(&["--tweak"], ""),
+            Err("missing value for --tweak".to_owned())
+        );
+        assert_eq!(
+            build(&["--tweak", "-1"], ""),
+            Err("invalid nTweak for --tweak".to_owned())
+        );
+        assert_eq!(
+            build(&["--fp-rate", "often"], ""),
+            Err("invalid false positives rate for --fp-rate".to_owned())
+        );
+        assert!(build(&["--fp-rate", "1.5"], "").is_err());
+        assert_eq!(
+            build(&["--flags", "some"], ""),
+            Err("unknown flags some".to_owned())
+        );
+        assert!(build(&["--verbose"], "")
+            .unwrap_err()
+            .starts_with("unknown option --verbose"));
+        assert!(build(&[], "abc")
+            .unwrap_err()
+            .starts_with("element abc is neither hex nor an address"));
+    }
+
+    /// Transaction spending `33..33:1` with input script pushing `abcd` and an output
+    /// script pushing `44`
+    fn raw_transaction() -> Vec<u8> {
+        let mut raw = vec![0x01, 0x00, 0x00, 0x00, 0x01];
+        raw.extend([0x33; 32]);
+        raw.extend(1u32.to_le_bytes());
+        raw.extend([0x03, 0x02, 0xAB, 0xCD]);
+        raw.extend(u32::MAX.to_le_bytes());
+        raw.push(0x01);
+        raw.extend(5000u64.to_le_bytes());
+        raw.extend([0x03, 0x4C, 0x01, 0x44]);
+        raw.extend(0u32.to_le_bytes());
+        raw
+    }
+
+    fn filter_hex(build: impl FnOnce(BloomFilterBuilder) -> BloomFilterBuilder) -> String {
+        let builder =
+            BloomFilterBuilder::new_n_tweak(3, 0.0001, 0).expect("parameters are correct");
+        build(builder).build().to_rpc_hex()
+    }
+
+    #[test]
+    fn match_reasons() {
+        let raw = raw_transaction();
+        let tx = Transaction::from_bytes(&raw).expect("transaction is valid");
+        let txid = display_txid(&tx.txid);
+        let match_with = |filter: String| match_transaction(&args(&[&filter, &encode_hex(&raw)]));
+
+        assert_eq!(
+            match_with(filter_hex(|builder| builder.add_element(&tx.txid))),
+            Ok(format!("{txid} matches\n  txid"))
+        );
+        assert_eq!(
+            match_with(filter_hex(|builder| builder.add_element(&[0x44]))),
+            Ok(format!("{txid} matches\n  output 0 element 44"))
+        );
+        assert_eq!(
+            match_with(filter_hex(|builder| builder.add_outpoint(&[0x33; 32], 1))),
+            Ok(format!(
+                "{txid} matches\n  spent outpoint {}:1",
+                "33".repeat(32)
+            ))
+        );
+        assert_eq!(
+            match_with(filter_hex(|builder| builder.add_element(&[0xAB, 0xCD]))),
+            Ok(format!("{txid} matches\n  input 0 element abcd"))
+        );
+        assert_eq!(
+            match_with(filter_hex(|builder| builder
+                .add_element(&tx.txid)
+                .add_element(&[0x44]))),
+            Ok(format!("{txid} matches\n  txid\n  output 0 element 44"))
+        );
+        assert_eq!(
+            match_with(filter_hex(|builder| builder.add_element(&[0x55]))),
+            Ok(format!("{txid} does not match"))
+        );
+    }
+
+    #[test]
+    fn match_errors() {
+        let raw = encode_hex(&raw_transaction());
+        let filter = filter_hex(|builder| builder);
+
+        assert_eq!(match_transaction(&args(&[&filter])), Err(USAGE.to_owned()));
+        assert_eq!(
+            match_transaction(&args(&[&filter, &raw, "extra"])),
+            Err(USAGE.to_owned())
+        );
+        assert_eq!(
+            match_transaction(&args(&[&filter, "0g"])),
+            Err("transaction is not hex".to_owned())
+        );
+        assert!(match_transaction(&args(&[&filter, &raw[..raw.len() - 2]]))
+            .unwrap_err()
+            .starts_with("invalid transaction"));
+        assert!(match_transaction(&args(&["zz", &raw]))
+            .unwrap_err()
+            .starts_with("invalid filter"));
+    }
+
+    #[test]
+    fn flags() {
+        for (names, expected) in [
+            (["none", "0"], BloomFlags::UpdateNone),
+            (["all", "1"], BloomFlags::UpdateAll),
+            (["p2pubkey-only", "2"], BloomFlags::UpdateP2PubkeyOnly),
+        ] {
+            for name in names {
+                assert_eq!(parse_flags(name), Ok(expected));
+            }
+        }
+        assert!(parse_flags("3").is_err());
+    }
+
+    #[test]
+    fn hex() {
+        assert_eq!(decode_hex("00ffAB"), Some(vec![0x00, 0xFF, 0xAB]));
+        assert_eq!(decode_hex(""), Some(vec![]));
+        assert_eq!(decode_hex("abc"), None);
+        assert_eq!(decode_hex("zz"), None);
+        assert_eq!(encode_hex(&[0x00, 0xFF, 0xAB]), "00ffab");
+    }
+}
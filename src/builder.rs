@@ -1,27 +1,122 @@
 //! Bloom filter builder module.
 
-use bitvec::vec::BitVec;
+use std::num::NonZeroUsize;
 
-use crate::{hasher::Hasher, BloomFilter};
+use crate::{
+    filter::{self, FilterBits},
+    hasher::Hasher,
+    hashes, script, BloomFilter, BloomFilterData, BloomFlags, InvalidFilterData,
+    MAX_BLOOM_FILTER_SIZE, MAX_HASH_FUNCS,
+};
 
 /// Error type to indicate incompatible Bloom filter parameters.
-#[derive(Debug, thiserror::Error)]
-#[error(
-    "max filter size exceeded, try increasing FP rate and/or lower the number of expected items"
-)]
-pub struct BadFilterParameters;
+#[derive(Debug, thiserror::Error, PartialEq)]
+pub enum BadFilterParameters {
+    /// Filter size needed to satisfy the false positives rate exceeds the limit
+    #[error(
+        "filter size of {computed} bytes exceeds the maximum of {max} bytes, try increasing FP \
+         rate and/or lower the number of expected items"
+    )]
+    FilterTooLarge {
+        /// Computed filter size in bytes
+        computed: u64,
+        /// Maximum filter size in bytes
+        max: u64,
+    },
+    /// Filter size is zero bytes
+    #[error("filter size must be at least 1 byte")]
+    EmptyFilter,
+    /// Number of expected elements is zero
+    #[error("number of expected elements must be positive")]
+    ZeroElements,
+    /// False positives rate is not within `(0, 1)`
+    #[error("false positives rate {0} is not within (0, 1)")]
+    InvalidFalsePositiveRate(f64),
+    /// Number of hash functions exceeds the limit
+    #[error("{computed} hash functions exceed the maximum of {max}, try increasing FP rate")]
+    TooManyHashFunctions {
+        /// Computed number of hash functions
+        computed: u32,
+        /// Maximum number of hash functions
+        max: u32,
+    },
+    /// Number of hash functions is zero
+    #[error("at least one hash function is required")]
+    ZeroHashFunctions,
+}
+
+/// Error type to indicate that a Bloom filter already holds as many elements as it was
+/// sized for.
+#[derive(Debug, thiserror::Error, PartialEq)]
+#[error("filter capacity of {capacity} elements exceeded, false positives rate degrades")]
+pub struct CapacityExceeded {
+    /// Number of elements the filter was sized for
+    pub capacity: u32,
+}
+
+/// Natural logarithm of 2 squared, as defined in Dash Core
+const LN2_SQUARED: f64 = 0.480_453_013_918_201_4;
+
+/// One in parts per million
+const PPM: u32 = 1_000_000;
+
+/// Fractional bits of fixed point numbers in integer parameter math
+const FRAC_BITS: u32 = 32;
+
+/// `log2(e)` in fixed point
+const LOG2_E_FIXED: u64 = 6_196_328_019;
+
+/// `ln(2)` in fixed point
+const LN_2_FIXED: u64 = 2_977_044_472;
+
+/// Base 2 logarithm of a positive integer in fixed point, computed bit by bit by squaring
+/// the mantissa
+const fn fixed_log2(x: u32) -> u64 {
+    let int_part = 31 - x.leading_zeros();
+    let mut result = (int_part as u64) << FRAC_BITS;
+
+    // mantissa within [1, 2)
+    let mut mantissa = ((x as u128) << FRAC_BITS) >> int_part;
+    let mut bit = 1 << (FRAC_BITS - 1);
+    while bit > 0 {
+        mantissa = (mantissa * mantissa) >> FRAC_BITS;
+        if mantissa >= 2 << FRAC_BITS {
+            mantissa >>= 1;
+            result |= bit;
+        }
+        bit >>= 1;
+    }
+    result
+}
+
+/// How filter parameters are derived from the number of elements and false positives rate
+#[derive(Clone, Copy, PartialEq)]
+enum Sizing {
+    /// Clamp parameters to BIP-37 limits, failing only if the size limit is exceeded
+    Default,
+    /// Fail if any parameter is out of BIP-37 limits
+    Strict,
+    /// Same as default, but without the size limit
+    Local,
+    /// Dash Core's `CBloomFilter` arithmetic
+    CoreCompat,
+}
 
 /// Builder structure for Bloom filter
 pub struct BloomFilterBuilder {
-    n_tweak: u32,
-    filter_bits: BitVec<u8>,
-    hasher: Hasher,
+    pub(crate) n_tweak: u32,
+    pub(crate) filter_bits: FilterBits,
+    pub(crate) hasher: Hasher,
+    n_elements: u32,
+    elements_count: u32,
+    max_size: usize,
 }
 
 impl BloomFilterBuilder {
     /// Create new Bloom filter builder with `n_elements` maximum expected elements that
     /// should satisfy provided false positives rate.  [BadFilterParameters] returned if
-    /// the false positives rate cannot be satisfied for that number of items.
+    /// the false positives rate cannot be satisfied for that number of items, or the
+    /// inputs are invalid: zero elements or false positives rate not within `(0, 1)`.
     pub fn new(n_elements: u32, false_positives_rate: f64) -> Result<Self, BadFilterParameters> {
         Self::new_n_tweak(n_elements, false_positives_rate, 0)
     }
@@ -33,76 +128,611 @@ impl BloomFilterBuilder {
         false_positives_rate: f64,
         n_tweak: u32,
     ) -> Result<Self, BadFilterParameters> {
-        let filter_size_bytes = Self::filter_size(n_elements, false_positives_rate)?;
+        Self::with_params(n_elements, false_positives_rate, n_tweak, Sizing::Default)
+    }
 
-        let n_hashes = Self::hash_fns_number(n_elements, filter_size_bytes);
-        let hash_seeds = (0..n_hashes)
-            .map(|i| i.overflowing_mul(0xFBA4C795).0 + n_tweak)
-            .collect();
+    /// Create new Bloom filter builder like at [Self::new_n_tweak] with `nTweak` drawn
+    /// from the random number generator, as BIP-37 recommends for every new filter
+    #[cfg(feature = "rand")]
+    pub fn new_random_tweak(
+        n_elements: u32,
+        false_positives_rate: f64,
+        rng: &mut impl rand_core::RngCore,
+    ) -> Result<Self, BadFilterParameters> {
+        Self::new_n_tweak(n_elements, false_positives_rate, rng.next_u32())
+    }
 
-        let data = BitVec::<u8>::repeat(false, filter_size_bytes * 8);
+    /// Create new Bloom filter builder like at [Self::new_n_tweak], except
+    /// [BadFilterParameters] is returned when the computed filter size is zero bytes or
+    /// the computed number of hash functions falls outside of `1..=50` instead of
+    /// clamping them.
+    pub fn new_strict(
+        n_elements: u32,
+        false_positives_rate: f64,
+        n_tweak: u32,
+    ) -> Result<Self, BadFilterParameters> {
+        Self::with_params(n_elements, false_positives_rate, n_tweak, Sizing::Strict)
+    }
+
+    /// Create new Bloom filter builder like at [Self::new_n_tweak], except the BIP-37
+    /// limit of 36,000 bytes on the filter size is not enforced.  Such filters are meant
+    /// for local use only, as peers will reject them and so will
+    /// [BloomFilter::from_data](crate::BloomFilter::from_data).
+    pub fn new_local(
+        n_elements: u32,
+        false_positives_rate: f64,
+        n_tweak: u32,
+    ) -> Result<Self, BadFilterParameters> {
+        Self::with_params(n_elements, false_positives_rate, n_tweak, Sizing::Local)
+    }
+
+    /// Create new Bloom filter builder computing parameters exactly like Dash Core's
+    /// `CBloomFilter` does, so the resulting filter is byte-for-byte identical to filters
+    /// produced by Dash Core and dashj for the same inputs: the size is clamped to the
+    /// BIP-37 limit instead of failing and the bits per element ratio is rounded down
+    /// before computing the number of hash functions.
+    ///
+    /// Dash Core produces filters matching everything when the size or the number of hash
+    /// functions rounds down to zero, [BadFilterParameters] is returned instead.
+    pub fn new_core_compat(
+        n_elements: u32,
+        false_positives_rate: f64,
+        n_tweak: u32,
+    ) -> Result<Self, BadFilterParameters> {
+        Self::with_params(
+            n_elements,
+            false_positives_rate,
+            n_tweak,
+            Sizing::CoreCompat,
+        )
+    }
+
+    fn with_params(
+        n_elements: u32,
+        false_positives_rate: f64,
+        n_tweak: u32,
+        sizing: Sizing,
+    ) -> Result<Self, BadFilterParameters> {
+        Self::check_inputs(n_elements, false_positives_rate)?;
 
-        let hasher = Hasher {
-            filter_bits_len: data.len(),
-            hash_seeds,
+        let max_size = if sizing == Sizing::Local {
+            usize::MAX
+        } else {
+            MAX_BLOOM_FILTER_SIZE
         };
+        let strict = sizing == Sizing::Strict;
 
-        Ok(BloomFilterBuilder {
+        let (filter_size_bytes, n_hashes) = if sizing == Sizing::CoreCompat {
+            Self::core_compat_params(n_elements, false_positives_rate)?
+        } else {
+            let filter_size_bytes =
+                match Self::filter_size(n_elements, false_positives_rate, max_size)? {
+                    0 if strict => return Err(BadFilterParameters::EmptyFilter),
+                    size => size.max(1),
+                };
+
+            let n_hashes = Self::hash_fns_number(n_elements, filter_size_bytes);
+            let n_hashes = match n_hashes {
+                1..=MAX_HASH_FUNCS => n_hashes,
+                0 if strict => return Err(BadFilterParameters::ZeroHashFunctions),
+                _ if strict => {
+                    return Err(BadFilterParameters::TooManyHashFunctions {
+                        computed: n_hashes,
+                        max: MAX_HASH_FUNCS,
+                    })
+                }
+                _ => n_hashes.clamp(1, MAX_HASH_FUNCS),
+            };
+            (filter_size_bytes, n_hashes)
+        };
+
+        Ok(Self::from_params(
+            n_elements,
+            filter_size_bytes,
+            n_hashes,
+            n_tweak,
+            max_size,
+        ))
+    }
+
+    fn from_params(
+        n_elements: u32,
+        filter_size_bytes: usize,
+        n_hashes: u32,
+        n_tweak: u32,
+        max_size: usize,
+    ) -> Self {
+        let data = FilterBits::repeat(false, filter_size_bytes * 8);
+        let hasher = Hasher::new(n_hashes, n_tweak, data.len());
+
+        BloomFilterBuilder {
             n_tweak,
             filter_bits: data,
             hasher,
-        })
+            n_elements,
+            elements_count: 0,
+            max_size,
+        }
+    }
+
+    /// Create new Bloom filter builder like at [Self::new_n_tweak] with the false
+    /// positives rate given in parts per million, see [Self::params_ppm].
+    pub fn new_ppm(
+        n_elements: u32,
+        false_positives_ppm: u32,
+        n_tweak: u32,
+    ) -> Result<Self, BadFilterParameters> {
+        let (filter_size_bytes, n_hashes) = Self::params_ppm(n_elements, false_positives_ppm)?;
+        Ok(Self::from_params(
+            n_elements,
+            filter_size_bytes,
+            n_hashes,
+            n_tweak,
+            MAX_BLOOM_FILTER_SIZE,
+        ))
+    }
+
+    /// Compute filter size in bytes and number of hash functions like [Self::new_n_tweak]
+    /// does, with the false positives rate given in parts per million.  Only integer
+    /// arithmetic is used, so results are the same on every platform and can be computed
+    /// in const context.  They may differ from the floating point ones by a byte or a
+    /// hash function where the formulas are close to rounding.
+    ///
+    /// [BadFilterParameters] returned if there are no elements, the rate is not within
+    /// `1..=999_999` or the filter would exceed BIP-37 size limit.
+    pub const fn params_ppm(
+        n_elements: u32,
+        false_positives_ppm: u32,
+    ) -> Result<(usize, u32), BadFilterParameters> {
+        if n_elements == 0 {
+            return Err(BadFilterParameters::ZeroElements);
+        }
+        if false_positives_ppm == 0 || false_positives_ppm >= PPM {
+            return Err(BadFilterParameters::InvalidFalsePositiveRate(
+                false_positives_ppm as f64 / PPM as f64,
+            ));
+        }
+
+        // -n * ln(p) / ln(2)^2 = n * log2(1 / p) * log2(e)
+        let log2_inverse_rate = fixed_log2(PPM) - fixed_log2(false_positives_ppm);
+        let filter_size_bits =
+            (n_elements as u128 * log2_inverse_rate as u128 * LOG2_E_FIXED as u128)
+                >> (2 * FRAC_BITS);
+        let filter_size_bytes = filter_size_bits as u64 / 8;
+        if filter_size_bytes > MAX_BLOOM_FILTER_SIZE as u64 {
+            return Err(BadFilterParameters::FilterTooLarge {
+                computed: filter_size_bytes,
+                max: MAX_BLOOM_FILTER_SIZE as u64,
+            });
+        }
+        let filter_size_bytes = if filter_size_bytes == 0 {
+            1
+        } else {
+            filter_size_bytes
+        };
+
+        let n_hashes = ((filter_size_bytes * 8 * LN_2_FIXED) / n_elements as u64) >> FRAC_BITS;
+        let n_hashes = match n_hashes {
+            0 => 1,
+            n if n > MAX_HASH_FUNCS as u64 => MAX_HASH_FUNCS,
+            n => n as u32,
+        };
+
+        Ok((filter_size_bytes as usize, n_hashes))
+    }
+
+    /// Create Bloom filter builder from a persisted filter to continue adding elements to
+    /// it, see [BloomFilter::into_builder].  [InvalidFilterData] returned if the data
+    /// violates BIP-37 limits.
+    pub fn from_data(data: BloomFilterData) -> Result<Self, InvalidFilterData> {
+        BloomFilter::from_data(data).map(Self::from_filter)
+    }
+
+    /// Continue building the filter with its bits, `nTweak` and hash functions kept.  The
+    /// number of expected elements is taken as the number the filter is optimally sized
+    /// for and the number of added elements is estimated from the set bits.
+    pub(crate) fn from_filter(mut filter: BloomFilter) -> Self {
+        let n_elements = (filter.bit_len() as f64 * std::f64::consts::LN_2
+            / filter.n_hash_funcs().max(1) as f64) as u32;
+        let elements_count = filter.estimated_element_count().round() as u32;
+        let max_size = if filter.size_bytes() > MAX_BLOOM_FILTER_SIZE {
+            usize::MAX
+        } else {
+            MAX_BLOOM_FILTER_SIZE
+        };
+
+        BloomFilterBuilder {
+            n_tweak: filter.n_tweak,
+            filter_bits: std::mem::take(&mut filter.filter_bits),
+            hasher: std::mem::take(&mut filter.hasher),
+            n_elements: n_elements.max(1),
+            elements_count,
+            max_size,
+        }
+    }
+
+    /// Get filter size in bytes
+    pub fn filter_size_bytes(&self) -> usize {
+        self.filter_bits.len() / 8
+    }
+
+    /// Get number of hash functions
+    pub fn n_hash_funcs(&self) -> u32 {
+        self.hasher.hash_seeds.len() as u32
+    }
+
+    /// Get false positives rate the filter is expected to have once the number of
+    /// elements it was created for are added, which may differ from the requested one
+    /// due to rounding and limits
+    pub fn expected_fp_rate(&self) -> f64 {
+        filter::false_positives_rate(self.n_hash_funcs(), self.filter_bits.len(), self.n_elements)
+    }
+
+    /// Override the computed filter size.  [BadFilterParameters] returned if the size is
+    /// zero or exceeds BIP-37 limit (unless the builder was created with
+    /// [Self::new_local]).
+    ///
+    /// The filter is reset, so this should be called before adding elements.
+    pub fn with_size_bytes(mut self, size_bytes: usize) -> Result<Self, BadFilterParameters> {
+        if size_bytes == 0 {
+            return Err(BadFilterParameters::EmptyFilter);
+        }
+        if size_bytes > self.max_size {
+            return Err(BadFilterParameters::FilterTooLarge {
+                computed: size_bytes as u64,
+                max: self.max_size as u64,
+            });
+        }
+
+        self.filter_bits = FilterBits::repeat(false, size_bytes * 8);
+        self.elements_count = 0;
+        self.hasher.filter_bits_len = self.filter_bits.len();
+        Ok(self)
+    }
+
+    /// Override the computed number of hash functions.  [BadFilterParameters] returned if
+    /// the number falls outside of `1..=50`.
+    ///
+    /// The filter is reset, so this should be called before adding elements.
+    pub fn with_hash_funcs(mut self, n_hash_funcs: u32) -> Result<Self, BadFilterParameters> {
+        if n_hash_funcs == 0 {
+            return Err(BadFilterParameters::ZeroHashFunctions);
+        }
+        if n_hash_funcs > MAX_HASH_FUNCS {
+            return Err(BadFilterParameters::TooManyHashFunctions {
+                computed: n_hash_funcs,
+                max: MAX_HASH_FUNCS,
+            });
+        }
+
+        self.filter_bits.fill(false);
+        self.elements_count = 0;
+        self.hasher = Hasher::new(n_hash_funcs, self.n_tweak, self.filter_bits.len());
+        Ok(self)
     }
 
     fn filter_size(
         n_elements: u32,
         false_positives_rate: f64,
+        max_size: usize,
     ) -> Result<usize, BadFilterParameters> {
         let filter_size =
-            ((-1.0 / 2.0_f64.ln().powi(2) * n_elements as f64 * false_positives_rate.ln()) as u64
-                / 8)
-            .try_into();
+            (-1.0 / 2.0_f64.ln().powi(2) * n_elements as f64 * false_positives_rate.ln()) as u64
+                / 8;
+
+        match usize::try_from(filter_size) {
+            Ok(s) if s <= max_size => Ok(s),
+            _ => Err(BadFilterParameters::FilterTooLarge {
+                computed: filter_size,
+                max: max_size as u64,
+            }),
+        }
+    }
+
+    /// Check that the number of elements is positive and the false positives rate is
+    /// within `(0, 1)`
+    pub(crate) fn check_inputs(
+        n_elements: u32,
+        false_positives_rate: f64,
+    ) -> Result<(), BadFilterParameters> {
+        if n_elements == 0 {
+            return Err(BadFilterParameters::ZeroElements);
+        }
+        Self::check_fp_rate(false_positives_rate)
+    }
+
+    pub(crate) fn check_fp_rate(false_positives_rate: f64) -> Result<(), BadFilterParameters> {
+        if !(false_positives_rate > 0.0 && false_positives_rate < 1.0) {
+            return Err(BadFilterParameters::InvalidFalsePositiveRate(
+                false_positives_rate,
+            ));
+        }
+        Ok(())
+    }
+
+    fn core_compat_params(
+        n_elements: u32,
+        false_positives_rate: f64,
+    ) -> Result<(usize, u32), BadFilterParameters> {
+        let filter_size_bits =
+            ((-1.0 / LN2_SQUARED * n_elements as f64 * false_positives_rate.ln()) as u32)
+                .min(MAX_BLOOM_FILTER_SIZE as u32 * 8);
+        let filter_size_bytes = filter_size_bits / 8;
+        if filter_size_bytes == 0 {
+            return Err(BadFilterParameters::EmptyFilter);
+        }
 
-        match filter_size {
-            Ok(s) if s < 36_000 => Ok(s),
-            _ => Err(BadFilterParameters),
+        let n_hashes = (((filter_size_bytes * 8 / n_elements) as f64 * std::f64::consts::LN_2)
+            as u32)
+            .min(MAX_HASH_FUNCS);
+        if n_hashes == 0 {
+            return Err(BadFilterParameters::ZeroHashFunctions);
         }
+
+        Ok((filter_size_bytes as usize, n_hashes))
     }
 
     fn hash_fns_number(n_elements: u32, filter_size: usize) -> u32 {
         ((filter_size * 8) as f64 / n_elements as f64 * 2_f64.ln()) as u32
     }
 
+    fn insert(&mut self, element: &[u8]) {
+        self.hasher.insert(&mut self.filter_bits, element);
+        self.elements_count = self.elements_count.saturating_add(1);
+    }
+
+    fn insert_parts(&mut self, parts: &[&[u8]]) {
+        self.hasher.insert_parts(&mut self.filter_bits, parts);
+        self.elements_count = self.elements_count.saturating_add(1);
+    }
+
     /// Add element to Bloom filter
     pub fn add_element(mut self, element: &[u8]) -> Self {
-        let indexes = self.hasher.hash_indexes(element);
-        for hash in indexes {
-            *self
-                .filter_bits
-                .get_mut(hash)
-                .expect("hash result is normalized") = true;
+        self.insert(element);
+        self
+    }
+
+    /// Add element to Bloom filter unless it already holds as many elements as it was
+    /// sized for, in which case [CapacityExceeded] is returned and the element is not
+    /// added
+    pub fn try_add_element(&mut self, element: &[u8]) -> Result<(), CapacityExceeded> {
+        if self.elements_count >= self.n_elements {
+            return Err(CapacityExceeded {
+                capacity: self.n_elements,
+            });
+        }
+        self.insert(element);
+        Ok(())
+    }
+
+    /// Get number of elements added so far, duplicates included
+    pub fn elements_count(&self) -> u32 {
+        self.elements_count
+    }
+
+    /// Add transaction outpoint to Bloom filter.  `txid` is expected in internal byte
+    /// order, i.e. reversed relative to its usual hex representation.
+    pub fn add_outpoint(mut self, txid: &[u8; 32], vout: u32) -> Self {
+        self.insert_parts(&[txid, &vout.to_le_bytes()]);
+        self
+    }
+
+    /// Add public key and its HASH160 to Bloom filter, so both pay-to-pubkey and
+    /// pay-to-pubkey-hash outputs are matched
+    pub fn add_pubkey(self, pubkey: &[u8]) -> Self {
+        self.add_element(pubkey)
+            .add_pubkey_hash(&hashes::hash160(pubkey))
+    }
+
+    /// Add public key hash to Bloom filter
+    pub fn add_pubkey_hash(self, pubkey_hash: &[u8; 20]) -> Self {
+        self.add_element(pubkey_hash)
+    }
+
+    /// Add hash160 of a Dash P2PKH or P2SH address to Bloom filter
+    #[cfg(feature = "addresses")]
+    pub fn add_address(self, address: &str) -> Result<Self, crate::InvalidAddress> {
+        let hash = crate::address::decode_hash160(address)?;
+        Ok(self.add_element(&hash))
+    }
+
+    /// Derive the first `external_gap` receive and `internal_gap` change public keys of
+    /// the extended public key and add their hashes to Bloom filter.  The filter should
+    /// be sized for at least `external_gap + internal_gap` elements.
+    #[cfg(feature = "bip32")]
+    pub fn add_xpub(
+        mut self,
+        xpub: &str,
+        external_gap: u32,
+        internal_gap: u32,
+    ) -> Result<Self, crate::InvalidXpub> {
+        let xpub: bip32::XPub = xpub.parse()?;
+        for (chain, gap) in [(0, external_gap), (1, internal_gap)] {
+            for pubkey in crate::xpub::derive_chain(&xpub, chain, gap)? {
+                self = self.add_pubkey_hash(&hashes::hash160(&pubkey?));
+            }
+        }
+        Ok(self)
+    }
+
+    /// Add data elements of all outputs described by the output descriptor to Bloom
+    /// filter, deriving ranged descriptors for every index of `range`.  For each key
+    /// both the public key and its hash are added, for `sh(multi(...))` the redeem script
+    /// hash and all public keys are added.
+    #[cfg(feature = "descriptors")]
+    pub fn add_descriptor(
+        self,
+        descriptor: &str,
+        range: std::ops::Range<u32>,
+    ) -> Result<Self, crate::InvalidDescriptor> {
+        let elements = crate::descriptor::data_elements(descriptor, range)?;
+        Ok(self.add_elements(elements))
+    }
+
+    /// Get the number of decoy elements to add on top of the elements added so far, so
+    /// the false positives rate rises to `target_fp_rate`.  The higher the rate, the more
+    /// unrelated addresses a peer sees matching the filter, so the less certain it is
+    /// which of them belong to the wallet.  Zero is returned if the rate is already
+    /// reached.  [BadFilterParameters::InvalidFalsePositiveRate] returned if the target
+    /// rate is not within `(0, 1)`.
+    pub fn decoys_for_fp_rate(&self, target_fp_rate: f64) -> Result<u32, BadFilterParameters> {
+        Self::check_fp_rate(target_fp_rate)?;
+
+        let m = self.filter_bits.len() as f64;
+        let k = self.n_hash_funcs() as f64;
+        let total = -(m / k) * (1.0 - target_fp_rate.powf(1.0 / k)).ln();
+        Ok((total.ceil() as u32).saturating_sub(self.elements_count))
+    }
+
+    /// Add `count` random elements to Bloom filter, deliberately raising its false
+    /// positives rate to hide the real elements, see [Self::decoys_for_fp_rate]
+    #[cfg(feature = "rand")]
+    pub fn add_decoys(mut self, count: u32, rng: &mut impl rand_core::RngCore) -> Self {
+        let mut decoy = [0; 32];
+        for _ in 0..count {
+            rng.fill_bytes(&mut decoy);
+            self.insert(&decoy);
         }
+        self
+    }
+
+    /// Add all non-empty data elements of the script to Bloom filter, see
+    /// [script::push_data]
+    pub fn add_script_elements(self, script: &[u8]) -> Self {
+        self.add_elements(script::push_data(script).filter(|data| !data.is_empty()))
+    }
+
+    /// Add multiple elements to Bloom filter
+    pub fn add_elements<I>(mut self, elements: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: AsRef<[u8]>,
+    {
+        self.extend(elements);
+        self
+    }
+
+    /// Add multiple elements to Bloom filter like at [Self::add_elements], hashing them
+    /// on the rayon thread pool.  Every thread sets bits in its own copy of the filter
+    /// and the copies are merged, so this pays off for large numbers of elements.
+    #[cfg(feature = "rayon")]
+    pub fn add_elements_par<T>(mut self, elements: &[T]) -> Self
+    where
+        T: AsRef<[u8]> + Sync,
+    {
+        use rayon::prelude::*;
+
+        let bit_len = self.filter_bits.len();
+        let hasher = &self.hasher;
+        let bits = elements
+            .par_iter()
+            .fold(
+                || FilterBits::repeat(false, bit_len),
+                |mut bits, element| {
+                    hasher.insert(&mut bits, element.as_ref());
+                    bits
+                },
+            )
+            .reduce(
+                || FilterBits::repeat(false, bit_len),
+                |mut bits, other| {
+                    bits |= &other;
+                    bits
+                },
+            );
+
+        self.filter_bits |= &bits;
+        self.count_added(elements.len());
+        self
+    }
+
+    /// Add multiple elements to Bloom filter like at [Self::add_elements], splitting them
+    /// across `n_threads` scoped threads.  Each thread sets bits in its own bit array and
+    /// the arrays are OR-ed at the end, so there is no contention and the filter is the
+    /// same as if the elements were added serially.
+    pub fn add_elements_threaded<T>(mut self, elements: &[T], n_threads: NonZeroUsize) -> Self
+    where
+        T: AsRef<[u8]> + Sync,
+    {
+        let chunk_len = elements.len().div_ceil(n_threads.get()).max(1);
+        let bit_len = self.filter_bits.len();
+        let hasher = &self.hasher;
 
+        let partials: Vec<FilterBits> = std::thread::scope(|scope| {
+            let workers: Vec<_> = elements
+                .chunks(chunk_len)
+                .map(|chunk| {
+                    scope.spawn(move || {
+                        let mut bits = FilterBits::repeat(false, bit_len);
+                        for element in chunk {
+                            hasher.insert(&mut bits, element.as_ref());
+                        }
+                        bits
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .map(|worker| worker.join().expect("hashing doesn't panic"))
+                .collect()
+        });
+
+        for bits in &partials {
+            self.filter_bits |= bits;
+        }
+        self.count_added(elements.len());
         self
     }
 
+    fn count_added(&mut self, n: usize) {
+        let added = u32::try_from(n).unwrap_or(u32::MAX);
+        self.elements_count = self.elements_count.saturating_add(added);
+    }
+
     /// Finalize Bloom filter
     pub fn build(self) -> BloomFilter {
-        BloomFilter {
-            filter_bits: self.filter_bits,
-            n_tweak: self.n_tweak,
-            n_flags: 0,
-            hasher: self.hasher,
-        }
+        self.build_with_n_flags(BloomFlags::UpdateNone)
     }
 
     /// Finalize Bloom filter with `nFlags` setting.
-    pub fn build_with_n_flags(self, n_flags: u32) -> BloomFilter {
+    pub fn build_with_n_flags(mut self, n_flags: BloomFlags) -> BloomFilter {
         BloomFilter {
-            filter_bits: self.filter_bits,
+            filter_bits: std::mem::take(&mut self.filter_bits),
             n_tweak: self.n_tweak,
             n_flags,
-            hasher: self.hasher,
+            hasher: std::mem::take(&mut self.hasher),
+            pending_filteradd: None,
+            dirty: true,
+        }
+    }
+}
+
+/// Wipes the filter bits, `nTweak` and hash seeds, as they reveal what the wallet is
+/// watching.
+#[cfg(feature = "zeroize")]
+impl zeroize::Zeroize for BloomFilterBuilder {
+    fn zeroize(&mut self) {
+        self.filter_bits.as_raw_mut_slice().zeroize();
+        self.n_tweak.zeroize();
+        self.hasher.zeroize();
+        self.elements_count.zeroize();
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl Drop for BloomFilterBuilder {
+    fn drop(&mut self) {
+        zeroize::Zeroize::zeroize(self);
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl zeroize::ZeroizeOnDrop for BloomFilterBuilder {}
+
+impl<T: AsRef<[u8]>> Extend<T> for BloomFilterBuilder {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, elements: I) {
+        for element in elements {
+            self.insert(element.as_ref());
         }
     }
 }
@@ -0,0 +1,100 @@
+//! Counting Bloom filter supporting removals.
+
+use crate::{
+    filter::FilterBits, hasher::Hasher, BadFilterParameters, BloomFilter, BloomFilterBuilder,
+    BloomFlags,
+};
+
+/// Bloom filter keeping an 8-bit counter instead of a bit, which allows elements
+/// removal.  Counters saturate at 255 and are never decremented after that.
+///
+/// Sizing and hashing are the same as of [BloomFilter], so it can be exported for use
+/// on the wire with [Self::to_bloom_filter].
+#[derive(Debug, Clone)]
+pub struct CountingBloomFilter {
+    counters: Vec<u8>,
+    n_tweak: u32,
+    hasher: Hasher,
+}
+
+impl CountingBloomFilter {
+    /// Create new counting Bloom filter sized like at [BloomFilterBuilder::new_n_tweak]
+    pub fn new(
+        n_elements: u32,
+        false_positives_rate: f64,
+        n_tweak: u32,
+    ) -> Result<Self, BadFilterParameters> {
+        let mut builder =
+            BloomFilterBuilder::new_n_tweak(n_elements, false_positives_rate, n_tweak)?;
+
+        Ok(CountingBloomFilter {
+            counters: vec![0; builder.filter_bits.len()],
+            n_tweak,
+            hasher: std::mem::take(&mut builder.hasher),
+        })
+    }
+
+    /// Add element to the filter
+    pub fn insert(&mut self, element: &[u8]) {
+        for i in self.hasher.hash_indexes(element) {
+            self.counters[i] = self.counters[i].saturating_add(1);
+        }
+    }
+
+    /// Remove previously inserted element from the filter.  Returns `false` and leaves
+    /// the filter untouched if the element is definitely not in the filter.
+    ///
+    /// Removing an element that was never inserted but is a false positive introduces
+    /// false negatives.
+    pub fn remove(&mut self, element: &[u8]) -> bool {
+        if !self.probably_contains(element) {
+            return false;
+        }
+
+        for i in self.hasher.hash_indexes(element) {
+            if self.counters[i] != u8::MAX {
+                self.counters[i] -= 1;
+            }
+        }
+        true
+    }
+
+    /// Check if the filter possibly contains the element
+    pub fn probably_contains(&self, element: &[u8]) -> bool {
+        self.hasher
+            .hash_indexes(element)
+            .all(|i| self.counters[i] > 0)
+    }
+
+    /// Export the filter as a BIP-37 Bloom filter matching the same elements
+    pub fn to_bloom_filter(&self, n_flags: BloomFlags) -> BloomFilter {
+        BloomFilter {
+            filter_bits: self.counters.iter().map(|c| *c > 0).collect::<FilterBits>(),
+            n_tweak: self.n_tweak,
+            n_flags,
+            hasher: self.hasher.clone(),
+            pending_filteradd: None,
+            dirty: true,
+        }
+    }
+}
+
+/// Wipes the counters, `nTweak` and hash seeds.
+#[cfg(feature = "zeroize")]
+impl zeroize::Zeroize for CountingBloomFilter {
+    fn zeroize(&mut self) {
+        self.counters.as_mut_slice().zeroize();
+        self.n_tweak.zeroize();
+        self.hasher.zeroize();
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl Drop for CountingBloomFilter {
+    fn drop(&mut self) {
+        zeroize::Zeroize::zeroize(self);
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl zeroize::ZeroizeOnDrop for CountingBloomFilter {}
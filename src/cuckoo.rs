@@ -0,0 +1,155 @@
+//! Cuckoo filter for local membership tests with deletions.
+
+use crate::hasher::Hasher;
+
+const BUCKET_SIZE: usize = 4;
+const MAX_KICKS: usize = 500;
+
+/// Error type to indicate that a cuckoo filter has no room for an element.
+#[derive(Debug, thiserror::Error)]
+#[error("cuckoo filter is full")]
+pub struct CuckooFilterFull;
+
+/// Cuckoo filter storing 16-bit fingerprints in buckets of four.
+///
+/// Unlike Bloom filters it supports removal of elements at a comparable false
+/// positives rate (about `8 / 2^16`), but isn't wire-compatible with BIP-37.
+#[derive(Debug, Clone)]
+pub struct CuckooFilter {
+    buckets: Vec<[u16; BUCKET_SIZE]>,
+    len: usize,
+    victim: Option<(usize, u16)>,
+    hasher: Hasher,
+    rng_state: u32,
+}
+
+impl CuckooFilter {
+    /// Create new cuckoo filter able to hold at least `capacity` elements, `n_tweak`
+    /// seeds hash functions.
+    pub fn new(capacity: usize, n_tweak: u32) -> Self {
+        let n_buckets = capacity.div_ceil(BUCKET_SIZE).max(1).next_power_of_two();
+        CuckooFilter {
+            buckets: vec![[0; BUCKET_SIZE]; n_buckets],
+            len: 0,
+            victim: None,
+            hasher: Hasher::new(1, n_tweak, 0),
+            rng_state: n_tweak | 1,
+        }
+    }
+
+    fn fingerprint_and_index(&self, element: &[u8]) -> (u16, usize) {
+        let hash = self
+            .hasher
+            .hashes(element)
+            .next()
+            .expect("one hash function");
+        // Zero marks an empty slot
+        let fingerprint = ((hash >> 16) as u16).max(1);
+        (fingerprint, hash as usize & (self.buckets.len() - 1))
+    }
+
+    fn alt_index(&self, index: usize, fingerprint: u16) -> usize {
+        let hash = self
+            .hasher
+            .hashes(&fingerprint.to_le_bytes())
+            .next()
+            .expect("one hash function");
+        (index ^ hash as usize) & (self.buckets.len() - 1)
+    }
+
+    fn try_put(&mut self, index: usize, fingerprint: u16) -> bool {
+        match self.buckets[index].iter_mut().find(|slot| **slot == 0) {
+            Some(slot) => {
+                *slot = fingerprint;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn next_random(&mut self) -> u32 {
+        // xorshift32, only used to pick a fingerprint to relocate
+        self.rng_state ^= self.rng_state << 13;
+        self.rng_state ^= self.rng_state >> 17;
+        self.rng_state ^= self.rng_state << 5;
+        self.rng_state
+    }
+
+    /// Add element to the filter.  [CuckooFilterFull] returned if there is no room left.
+    pub fn insert(&mut self, element: &[u8]) -> Result<(), CuckooFilterFull> {
+        if self.victim.is_some() {
+            return Err(CuckooFilterFull);
+        }
+
+        let (mut fingerprint, i1) = self.fingerprint_and_index(element);
+        let i2 = self.alt_index(i1, fingerprint);
+        self.len += 1;
+        if self.try_put(i1, fingerprint) || self.try_put(i2, fingerprint) {
+            return Ok(());
+        }
+
+        let mut index = if self.next_random() & 1 == 0 { i1 } else { i2 };
+        for _ in 0..MAX_KICKS {
+            let slot = self.next_random() as usize % BUCKET_SIZE;
+            std::mem::swap(&mut fingerprint, &mut self.buckets[index][slot]);
+            index = self.alt_index(index, fingerprint);
+            if self.try_put(index, fingerprint) {
+                return Ok(());
+            }
+        }
+
+        // The element is stored, but the evicted fingerprint has no place anymore
+        self.victim = Some((index, fingerprint));
+        Ok(())
+    }
+
+    /// Check if the filter possibly contains the element
+    pub fn probably_contains(&self, element: &[u8]) -> bool {
+        let (fingerprint, i1) = self.fingerprint_and_index(element);
+        let i2 = self.alt_index(i1, fingerprint);
+
+        self.buckets[i1].contains(&fingerprint)
+            || self.buckets[i2].contains(&fingerprint)
+            || matches!(self.victim, Some((i, f)) if f == fingerprint && (i == i1 || i == i2))
+    }
+
+    /// Remove previously inserted element from the filter.  Returns `false` if the
+    /// element is definitely not in the filter.
+    pub fn remove(&mut self, element: &[u8]) -> bool {
+        let (fingerprint, i1) = self.fingerprint_and_index(element);
+        let i2 = self.alt_index(i1, fingerprint);
+
+        if matches!(self.victim, Some((i, f)) if f == fingerprint && (i == i1 || i == i2)) {
+            self.victim = None;
+            self.len -= 1;
+            return true;
+        }
+
+        for index in [i1, i2] {
+            if let Some(slot) = self.buckets[index].iter_mut().find(|f| **f == fingerprint) {
+                *slot = 0;
+                self.len -= 1;
+                if let Some((index, fingerprint)) = self.victim.take() {
+                    // Freed slot may host the evicted fingerprint again
+                    let alt = self.alt_index(index, fingerprint);
+                    if !self.try_put(index, fingerprint) && !self.try_put(alt, fingerprint) {
+                        self.victim = Some((index, fingerprint));
+                    }
+                }
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /// Number of elements in the filter
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Check if the filter has no elements
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
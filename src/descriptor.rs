@@ -0,0 +1,286 @@
+//! Output descriptors support.
+//!
+//! Only descriptors which are meaningful for Bloom filtering are supported: `pk`, `pkh`,
+//! `wpkh` and `sh(multi(...))`/`sh(sortedmulti(...))` with hex public keys or extended
+//! public keys, optionally ranged with a trailing `/*`.
+
+use std::ops::Range;
+
+use bip32::{ChildNumber, XPub};
+
+use crate::hashes;
+
+/// Error type to indicate an output descriptor that cannot be used to populate a Bloom
+/// filter.
+#[derive(Debug, thiserror::Error)]
+pub enum InvalidDescriptor {
+    /// Descriptor syntax is invalid or not supported
+    #[error("unsupported or malformed descriptor: {0}")]
+    Syntax(String),
+    /// Descriptor checksum doesn't match
+    #[error("descriptor checksum mismatch")]
+    Checksum,
+    /// Public key is invalid
+    #[error("invalid public key: {0}")]
+    Key(String),
+    /// Extended public key derivation failed
+    #[error("key derivation failed: {0}")]
+    Derivation(#[from] bip32::Error),
+}
+
+enum Key {
+    Fixed(Vec<u8>),
+    Extended {
+        xpub: XPub,
+        path: Vec<ChildNumber>,
+        ranged: bool,
+    },
+}
+
+enum Descriptor {
+    Pk(Key),
+    Pkh(Key),
+    Wpkh(Key),
+    ShMulti {
+        threshold: u8,
+        keys: Vec<Key>,
+        sorted: bool,
+    },
+}
+
+/// Compute Bloom filter data elements produced by the descriptor for every index in
+/// `range`, non-ranged descriptors produce their elements only once.
+pub(crate) fn data_elements(
+    descriptor: &str,
+    range: Range<u32>,
+) -> Result<Vec<Vec<u8>>, InvalidDescriptor> {
+    let descriptor = parse(strip_checksum(descriptor)?)?;
+    let indexes = if descriptor.is_ranged() { range } else { 0..1 };
+
+    let mut elements = Vec::new();
+    for index in indexes {
+        match &descriptor {
+            Descriptor::Pk(key) => elements.push(key.derive(index)?),
+            Descriptor::Pkh(key) | Descriptor::Wpkh(key) => {
+                let pubkey = key.derive(index)?;
+                elements.push(hashes::hash160(&pubkey).to_vec());
+                elements.push(pubkey);
+            }
+            Descriptor::ShMulti {
+                threshold,
+                keys,
+                sorted,
+            } => {
+                let mut pubkeys = keys
+                    .iter()
+                    .map(|key| key.derive(index))
+                    .collect::<Result<Vec<_>, _>>()?;
+                if *sorted {
+                    pubkeys.sort();
+                }
+                let redeem_script = multisig_script(*threshold, &pubkeys);
+                elements.push(hashes::hash160(&redeem_script).to_vec());
+                elements.extend(pubkeys);
+            }
+        }
+    }
+
+    Ok(elements)
+}
+
+impl Descriptor {
+    fn is_ranged(&self) -> bool {
+        match self {
+            Descriptor::Pk(key) | Descriptor::Pkh(key) | Descriptor::Wpkh(key) => key.is_ranged(),
+            Descriptor::ShMulti { keys, .. } => keys.iter().any(Key::is_ranged),
+        }
+    }
+}
+
+impl Key {
+    fn is_ranged(&self) -> bool {
+        matches!(self, Key::Extended { ranged: true, .. })
+    }
+
+    fn derive(&self, index: u32) -> Result<Vec<u8>, InvalidDescriptor> {
+        match self {
+            Key::Fixed(pubkey) => Ok(pubkey.clone()),
+            Key::Extended { xpub, path, ranged } => {
+                let mut xpub = xpub.clone();
+                for child in path {
+                    xpub = xpub.derive_child(*child)?;
+                }
+                if *ranged {
+                    xpub = xpub.derive_child(ChildNumber::new(index, false)?)?;
+                }
+                Ok(xpub.to_bytes().to_vec())
+            }
+        }
+    }
+}
+
+fn multisig_script(threshold: u8, pubkeys: &[Vec<u8>]) -> Vec<u8> {
+    const OP_1: u8 = 0x51;
+    const OP_CHECKMULTISIG: u8 = 0xAE;
+
+    let mut script = vec![OP_1 + threshold - 1];
+    for pubkey in pubkeys {
+        script.push(pubkey.len() as u8);
+        script.extend_from_slice(pubkey);
+    }
+    script.push(OP_1 + pubkeys.len() as u8 - 1);
+    script.push(OP_CHECKMULTISIG);
+    script
+}
+
+fn syntax_error(descriptor: &str) -> InvalidDescriptor {
+    InvalidDescriptor::Syntax(descriptor.to_owned())
+}
+
+fn parse(descriptor: &str) -> Result<Descriptor, InvalidDescriptor> {
+    let (name, args) = split_call(descriptor).ok_or_else(|| syntax_error(descriptor))?;
+    match name {
+        "pk" => Ok(Descriptor::Pk(parse_key(args)?)),
+        "pkh" => Ok(Descriptor::Pkh(parse_key(args)?)),
+        "wpkh" => Ok(Descriptor::Wpkh(parse_key(args)?)),
+        "sh" => {
+            let (name, args) = split_call(args).ok_or_else(|| syntax_error(descriptor))?;
+            let sorted = match name {
+                "multi" => false,
+                "sortedmulti" => true,
+                _ => return Err(syntax_error(descriptor)),
+            };
+            let mut args = args.split(',');
+            let threshold: u8 = args
+                .next()
+                .and_then(|t| t.parse().ok())
+                .ok_or_else(|| syntax_error(descriptor))?;
+            let keys = args.map(parse_key).collect::<Result<Vec<_>, _>>()?;
+            if threshold == 0 || threshold as usize > keys.len() || keys.len() > 16 {
+                return Err(syntax_error(descriptor));
+            }
+            Ok(Descriptor::ShMulti {
+                threshold,
+                keys,
+                sorted,
+            })
+        }
+        _ => Err(syntax_error(descriptor)),
+    }
+}
+
+/// Split `name(args)` into its parts.
+fn split_call(s: &str) -> Option<(&str, &str)> {
+    let (name, rest) = s.split_once('(')?;
+    Some((name, rest.strip_suffix(')')?))
+}
+
+fn parse_key(key: &str) -> Result<Key, InvalidDescriptor> {
+    // Key origin information doesn't affect derived keys
+    let key = match key.strip_prefix('[') {
+        Some(rest) => rest
+            .split_once(']')
+            .map(|(_, key)| key)
+            .ok_or_else(|| syntax_error(key))?,
+        None => key,
+    };
+
+    if let Some(pubkey) = crate::hex::decode(key) {
+        return match pubkey.len() {
+            33 | 65 => Ok(Key::Fixed(pubkey)),
+            _ => Err(InvalidDescriptor::Key(key.to_owned())),
+        };
+    }
+
+    let mut parts = key.split('/');
+    let xpub: XPub = parts
+        .next()
+        .and_then(|xpub| xpub.parse().ok())
+        .ok_or_else(|| InvalidDescriptor::Key(key.to_owned()))?;
+
+    let mut path = Vec::new();
+    let mut ranged = false;
+    for part in parts {
+        if ranged {
+            return Err(syntax_error(key));
+        }
+        if part == "*" {
+            ranged = true;
+            continue;
+        }
+        let index = part.parse().map_err(|_| syntax_error(key))?;
+        path.push(ChildNumber::new(index, false)?);
+    }
+
+    Ok(Key::Extended { xpub, path, ranged })
+}
+
+const INPUT_CHARSET: &str =
+    "0123456789()[],'/*abcdefgh@:$%{}IJKLMNOPQRSTUVWXYZ&+-.;<=>?!^_|~ijklmnopqrstuvwxyzABCDEFGH`#\"\\ ";
+const CHECKSUM_CHARSET: &[u8; 32] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+/// Remove the descriptor checksum suffix if present, verifying it.
+fn strip_checksum(descriptor: &str) -> Result<&str, InvalidDescriptor> {
+    match descriptor.split_once('#') {
+        Some((descriptor, checksum)) => {
+            if checksum.as_bytes() == compute_checksum(descriptor)? {
+                Ok(descriptor)
+            } else {
+                Err(InvalidDescriptor::Checksum)
+            }
+        }
+        None => Ok(descriptor),
+    }
+}
+
+fn compute_checksum(descriptor: &str) -> Result<[u8; 8], InvalidDescriptor> {
+    fn polymod(c: u64, val: u64) -> u64 {
+        let c0 = c >> 35;
+        let mut c = ((c & 0x7_FFFF_FFFF) << 5) ^ val;
+        for (bit, generator) in [
+            0xF5_DEE5_1989,
+            0xA9_FDCA_3312,
+            0x1B_AB10_E32D,
+            0x37_06B1_677A,
+            0x64_4D62_6FFD,
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            if c0 >> bit & 1 == 1 {
+                c ^= generator;
+            }
+        }
+        c
+    }
+
+    let mut c = 1;
+    let mut class = 0;
+    let mut class_count = 0;
+    for ch in descriptor.chars() {
+        let pos = INPUT_CHARSET
+            .find(ch)
+            .ok_or_else(|| syntax_error(descriptor))? as u64;
+        c = polymod(c, pos & 31);
+        class = class * 3 + (pos >> 5);
+        class_count += 1;
+        if class_count == 3 {
+            c = polymod(c, class);
+            class = 0;
+            class_count = 0;
+        }
+    }
+    if class_count > 0 {
+        c = polymod(c, class);
+    }
+    for _ in 0..8 {
+        c = polymod(c, 0);
+    }
+    c ^= 1;
+
+    let mut checksum = [0; 8];
+    for (j, ch) in checksum.iter_mut().enumerate() {
+        *ch = CHECKSUM_CHARSET[(c >> (5 * (7 - j)) & 31) as usize];
+    }
+    Ok(checksum)
+}
@@ -0,0 +1,128 @@
+//! C interface for linking the filter into C and C++ wallets.
+//!
+//! Filters are passed as opaque `BloomFilter` pointers created by [bip37_filter_new] or
+//! [bip37_filter_deserialize] and released by [bip37_filter_free].  Headers can be
+//! generated with cbindgen.
+//!
+//! The library is built as an `rlib` only, C libraries are built with
+//! `cargo rustc --release --features ffi --crate-type cdylib --crate-type staticlib`.
+
+use std::{ptr, slice};
+
+use crate::{BloomFilter, BloomFilterBuilder, BloomFlags};
+
+/// Create filter for `n_elements` with `false_positives_rate`, `n_tweak` and `n_flags`
+/// of `0`, `1` or `2`.  Returns null if the parameters are invalid or exceed BIP-37
+/// limits.
+#[no_mangle]
+pub extern "C" fn bip37_filter_new(
+    n_elements: u32,
+    false_positives_rate: f64,
+    n_tweak: u32,
+    n_flags: u8,
+) -> *mut BloomFilter {
+    let Ok(n_flags) = BloomFlags::try_from(n_flags) else {
+        return ptr::null_mut();
+    };
+    match BloomFilterBuilder::new_n_tweak(n_elements, false_positives_rate, n_tweak) {
+        Ok(builder) => Box::into_raw(Box::new(builder.build_with_n_flags(n_flags))),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Parse `filterload` payload of `len` bytes.  Returns null if it is malformed or
+/// exceeds BIP-37 limits.
+///
+/// # Safety
+///
+/// `data` must point to `len` readable bytes, it may be null if `len` is `0`.
+#[no_mangle]
+pub unsafe extern "C" fn bip37_filter_deserialize(data: *const u8, len: usize) -> *mut BloomFilter {
+    match BloomFilter::from_filterload_bytes(bytes(data, len)) {
+        Ok(filter) => Box::into_raw(Box::new(filter)),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Insert data element of `len` bytes.  Returns `false` if it was already covered by the
+/// filter.
+///
+/// # Safety
+///
+/// `filter` must be a live pointer returned by this module, not used from other
+/// threads at the same time.  `data` must point to `len` readable bytes, it may be null
+/// if `len` is `0`.
+#[no_mangle]
+pub unsafe extern "C" fn bip37_filter_add_element(
+    filter: *mut BloomFilter,
+    data: *const u8,
+    len: usize,
+) -> bool {
+    (*filter).insert(bytes(data, len))
+}
+
+/// Check if the filter probably contains data element of `len` bytes
+///
+/// # Safety
+///
+/// `filter` must be a live pointer returned by this module.  `data` must point to `len`
+/// readable bytes, it may be null if `len` is `0`.
+#[no_mangle]
+pub unsafe extern "C" fn bip37_filter_contains(
+    filter: *const BloomFilter,
+    data: *const u8,
+    len: usize,
+) -> bool {
+    (*filter).probably_contains(bytes(data, len))
+}
+
+/// Serialize the filter as `filterload` payload, writing its length to `out_len`.  The
+/// returned buffer is to be released with [bip37_filter_bytes_free].
+///
+/// # Safety
+///
+/// `filter` must be a live pointer returned by this module and `out_len` must be
+/// writable.
+#[no_mangle]
+pub unsafe extern "C" fn bip37_filter_serialize(
+    filter: *const BloomFilter,
+    out_len: *mut usize,
+) -> *mut u8 {
+    let bytes = (*filter).to_filterload_bytes().into_boxed_slice();
+    *out_len = bytes.len();
+    Box::into_raw(bytes).cast()
+}
+
+/// Release buffer returned by [bip37_filter_serialize].  Null is ignored.
+///
+/// # Safety
+///
+/// `data` and `len` must be as returned by [bip37_filter_serialize], and the buffer
+/// must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn bip37_filter_bytes_free(data: *mut u8, len: usize) {
+    if !data.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(data, len)));
+    }
+}
+
+/// Release the filter.  Null is ignored.
+///
+/// # Safety
+///
+/// `filter` must be a pointer returned by this module, and it must not be used
+/// afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn bip37_filter_free(filter: *mut BloomFilter) {
+    if !filter.is_null() {
+        drop(Box::from_raw(filter));
+    }
+}
+
+unsafe fn bytes<'a>(data: *const u8, len: usize) -> &'a [u8] {
+    if len == 0 {
+        &[]
+    } else {
+        slice::from_raw_parts(data, len)
+    }
+}
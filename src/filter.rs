@@ -1,40 +1,267 @@
 //! Bloom filter type and a data representation of it
 
+use std::{fmt, hash::Hash, str::FromStr};
+
 use bitvec::vec::BitVec;
 
-use crate::{hasher::Hasher, BadFilterParameters, BloomFilterBuilder};
+use crate::{
+    hasher::Hasher, hashes, hex, script, wire, BadFilterParameters, BloomFilterBuilder, BloomFlags,
+    Transaction,
+};
 
-/// Bloom filter fields exposed for serialization
-#[derive(Debug, Clone)]
+/// Maximum size of a Bloom filter in bytes as allowed by BIP-37.
+pub const MAX_BLOOM_FILTER_SIZE: usize = 36_000;
+
+/// Maximum number of hash functions as allowed by BIP-37.
+pub const MAX_HASH_FUNCS: u32 = 50;
+
+/// Maximum size of an element sent with `filteradd` as allowed by BIP-37.
+pub const MAX_FILTERADD_SIZE: usize = 520;
+
+/// Filter bits stored in 64-bit words for aligned word access, bit `i` is bit `i % 64`
+/// of word `i / 64`.  Read as little-endian bytes this is the BIP-37 layout, where bit
+/// `i` is bit `i % 8` of byte `i / 8`.
+pub(crate) type FilterBits = BitVec<u64>;
+
+/// Load filter bits from BIP-37 byte layout
+pub(crate) fn bits_from_bytes(bytes: &[u8]) -> FilterBits {
+    let words = bytes
+        .chunks(8)
+        .map(|chunk| {
+            let mut word = [0; 8];
+            word[..chunk.len()].copy_from_slice(chunk);
+            u64::from_le_bytes(word)
+        })
+        .collect();
+    let mut bits = FilterBits::from_vec(words);
+    bits.truncate(bytes.len() * 8);
+    bits
+}
+
+/// Store filter bits in BIP-37 byte layout
+pub(crate) fn bits_to_bytes(bits: &FilterBits) -> Vec<u8> {
+    bits.as_raw_slice()
+        .iter()
+        .flat_map(|word| word.to_le_bytes())
+        .take(bits.len() / 8)
+        .collect()
+}
+
+/// Check bit `i` of filter words
+pub(crate) fn test_bit(words: &[u64], i: usize) -> bool {
+    words[i / 64] & (1 << (i % 64)) != 0
+}
+
+/// Expected false positives rate of a filter of `bit_len` bits with `n_hash_funcs` hash
+/// functions holding `n_elements` elements: `(1 - e^(-kn/m))^k`
+pub(crate) fn false_positives_rate(n_hash_funcs: u32, bit_len: usize, n_elements: u32) -> f64 {
+    let k = n_hash_funcs as f64;
+    let m = bit_len as f64;
+    (1.0 - (-k * n_elements as f64 / m).exp()).powf(k)
+}
+
+/// Error type to indicate that [BloomFilterData] cannot be turned into a [BloomFilter].
+#[derive(Debug, thiserror::Error)]
+pub enum InvalidFilterData {
+    /// Filter byte array is empty
+    #[error("filter byte array is empty")]
+    EmptyFilter,
+    /// Filter byte array exceeds [MAX_BLOOM_FILTER_SIZE]
+    #[error("filter size of {0} bytes exceeds the maximum of {MAX_BLOOM_FILTER_SIZE} bytes")]
+    FilterTooLarge(usize),
+    /// Number of hash functions exceeds [MAX_HASH_FUNCS]
+    #[error("{0} hash functions exceed the maximum of {MAX_HASH_FUNCS}")]
+    TooManyHashFunctions(u32),
+    /// Serialized payload is truncated or contains extra bytes
+    #[error("malformed filterload payload")]
+    MalformedPayload,
+    /// String is not a valid hex encoding
+    #[error("invalid hex string")]
+    InvalidHex,
+}
+
+/// Error type to indicate that two filters cannot be combined because they were built
+/// with different parameters, so the same element maps to different bits in them.
+#[derive(Debug, PartialEq, thiserror::Error)]
+pub enum IncompatibleFilters {
+    /// Filters have different sizes
+    #[error("filter sizes of {0} and {1} bits differ")]
+    BitLength(usize, usize),
+    /// Filters have different `nTweak`
+    #[error("filter nTweak values {0} and {1} differ")]
+    NTweak(u32, u32),
+    /// Filters have different number of hash functions
+    #[error("filter hash functions numbers {0} and {1} differ")]
+    NHashFuncs(u32, u32),
+}
+
+/// Element hashed once for any number of filters sharing size, `nTweak` and number of
+/// hash functions, e.g. filters of many peers, see [BloomFilter::hash_element].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct HashedElement {
+    indexes: Vec<usize>,
+    bit_len: usize,
+    n_tweak: u32,
+}
+
+impl HashedElement {
+    /// Get indexes of the bits the element maps to
+    pub fn indexes(&self) -> &[usize] {
+        &self.indexes
+    }
+}
+
+/// Bloom filter fields exposed for serialization.  With the `serde` feature fields are
+/// named the way Dash Core RPC and dashj name them: `data`, `nHashFuncs`, `nTweak` and
+/// `nFlags`.  `data` is hex encoded in human-readable formats and raw bytes otherwise.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "camelCase")
+)]
+#[cfg_attr(
+    feature = "borsh",
+    derive(borsh::BorshSerialize, borsh::BorshDeserialize)
+)]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize),
+    rkyv(archived = ArchivedBloomFilter, bytecheck(verify))
+)]
 pub struct BloomFilterData {
     /// Bloom filter byte array
+    #[cfg_attr(feature = "serde", serde(rename = "data", with = "hex::serde"))]
     pub v_data: Vec<u8>,
     /// Number of hash functions used
     pub n_hash_funcs: u32,
     /// Hash functions initialization adjustment
     pub n_tweak: u32,
-    /// TODO
+    /// Filter update mode, see [BloomFlags]
     pub n_flags: u32,
 }
 
+#[cfg(feature = "cbor")]
+impl BloomFilterData {
+    /// Encode as a CBOR map with the same field names as with `serde`, `data` being a
+    /// byte string
+    pub fn to_cbor(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        ciborium::into_writer(self, &mut buf).expect("writing to a vector doesn't fail");
+        buf
+    }
+
+    /// Decode from CBOR produced by [Self::to_cbor].  [InvalidFilterData::MalformedPayload]
+    /// returned if the bytes are not a CBOR encoding of filter data.  Limits are not
+    /// checked until the data is turned into a [BloomFilter].
+    pub fn from_cbor(bytes: &[u8]) -> Result<Self, InvalidFilterData> {
+        ciborium::from_reader(bytes).map_err(|_| InvalidFilterData::MalformedPayload)
+    }
+}
+
+/// Archived [BloomFilterData] which can be queried in place, e.g. from a memory-mapped
+/// file, without deserializing it.
+#[cfg(feature = "rkyv")]
+impl ArchivedBloomFilter {
+    /// Check if the filter possibly contains the item like at
+    /// [BloomFilter::probably_contains], hashing it without allocations.  An empty filter
+    /// matches everything like in Dash Core.
+    ///
+    /// BIP-37 limits are checked when the archive is accessed with validation, e.g. with
+    /// `rkyv::access`.
+    pub fn probably_contains(&self, item: &[u8]) -> bool {
+        let bit_len = self.v_data.len() * 8;
+        if bit_len == 0 {
+            return true;
+        }
+        let n_tweak = self.n_tweak.to_native();
+        (0..self.n_hash_funcs.to_native()).all(|i| {
+            let index = crate::hasher::murmur3_32(item, crate::hasher::hash_seed(i, n_tweak))
+                as usize
+                % bit_len;
+            self.v_data[index / 8] & (1 << (index % 8)) != 0
+        })
+    }
+}
+
+/// Rejects archives exceeding [MAX_BLOOM_FILTER_SIZE] or [MAX_HASH_FUNCS] once on
+/// access, so queries don't have to check them
+#[cfg(feature = "rkyv")]
+unsafe impl<C> rkyv::bytecheck::Verify<C> for ArchivedBloomFilter
+where
+    C: rkyv::rancor::Fallible + ?Sized,
+    C::Error: rkyv::rancor::Source,
+{
+    fn verify(&self, _context: &mut C) -> Result<(), C::Error> {
+        if self.v_data.len() > MAX_BLOOM_FILTER_SIZE {
+            rkyv::rancor::fail!(InvalidFilterData::FilterTooLarge(self.v_data.len()));
+        }
+        let n_hash_funcs = self.n_hash_funcs.to_native();
+        if n_hash_funcs > MAX_HASH_FUNCS {
+            rkyv::rancor::fail!(InvalidFilterData::TooManyHashFunctions(n_hash_funcs));
+        }
+        Ok(())
+    }
+}
+
 impl From<BloomFilter> for BloomFilterData {
     fn from(bloom_filter: BloomFilter) -> Self {
         BloomFilterData {
-            v_data: bloom_filter.filter_bits.into(),
-            n_hash_funcs: bloom_filter.hasher.hash_seeds.len() as u32,
+            n_hash_funcs: bloom_filter.n_hash_funcs(),
+            v_data: bits_to_bytes(&bloom_filter.filter_bits),
             n_tweak: bloom_filter.n_tweak,
-            n_flags: bloom_filter.n_flags,
+            n_flags: u8::from(bloom_filter.n_flags).into(),
+        }
+    }
+}
+
+impl TryFrom<BloomFilterData> for BloomFilter {
+    type Error = InvalidFilterData;
+
+    fn try_from(data: BloomFilterData) -> Result<Self, Self::Error> {
+        if data.v_data.len() > MAX_BLOOM_FILTER_SIZE {
+            return Err(InvalidFilterData::FilterTooLarge(data.v_data.len()));
         }
+        BloomFilter::from_data_local(data)
     }
 }
 
 /// BIP-37 Bloom filter
 #[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(into = "BloomFilterData", try_from = "BloomFilterData")
+)]
 pub struct BloomFilter {
-    pub(crate) filter_bits: BitVec<u8>,
+    pub(crate) filter_bits: FilterBits,
     pub(crate) n_tweak: u32,
-    pub(crate) n_flags: u32,
+    pub(crate) n_flags: BloomFlags,
     pub(crate) hasher: Hasher,
+    pub(crate) pending_filteradd: Option<Vec<Vec<u8>>>,
+    pub(crate) dirty: bool,
+}
+
+/// Filters are equal if they have the same data and parameters, elements pending to be
+/// sent with `filteradd` and the dirty state are not compared.
+impl PartialEq for BloomFilter {
+    fn eq(&self, other: &Self) -> bool {
+        self.filter_bits == other.filter_bits
+            && self.n_tweak == other.n_tweak
+            && self.n_flags == other.n_flags
+            && self.hasher == other.hasher
+    }
+}
+
+impl Eq for BloomFilter {}
+
+impl Hash for BloomFilter {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.filter_bits.hash(state);
+        self.n_tweak.hash(state);
+        self.n_flags.hash(state);
+        self.hasher.hash(state);
+    }
 }
 
 impl BloomFilter {
@@ -55,15 +282,691 @@ impl BloomFilter {
         BloomFilterBuilder::new_n_tweak(n_elements, false_positives_rate, n_tweak)
     }
 
+    /// Turn the filter back into a builder keeping its bits, `nTweak` and hash functions,
+    /// so it can be extended with the builder API and rebuilt with other flags
+    pub fn into_builder(self) -> BloomFilterBuilder {
+        BloomFilterBuilder::from_filter(self)
+    }
+
+    /// Restore a Bloom filter from its data representation, rebuilding hash functions
+    /// from `nHashFuncs` and `nTweak`.  [InvalidFilterData] returned if the data violates
+    /// BIP-37 limits or the filter byte array is empty.  `nFlags` is interpreted like
+    /// Dash Core does, see [BloomFlags::from_masked].
+    pub fn from_data(data: BloomFilterData) -> Result<Self, InvalidFilterData> {
+        data.try_into()
+    }
+
+    /// Convert data like [Self::from_data] without the limit on the filter size,
+    /// for filters built with [BloomFilterBuilder::new_local]
+    pub(crate) fn from_data_local(data: BloomFilterData) -> Result<Self, InvalidFilterData> {
+        if data.v_data.is_empty() {
+            return Err(InvalidFilterData::EmptyFilter);
+        }
+        Self::from_data_allowing_empty(data)
+    }
+
+    /// Convert data like [Self::from_data_local], accepting an empty filter byte array
+    fn from_data_allowing_empty(data: BloomFilterData) -> Result<Self, InvalidFilterData> {
+        if data.n_hash_funcs > MAX_HASH_FUNCS {
+            return Err(InvalidFilterData::TooManyHashFunctions(data.n_hash_funcs));
+        }
+
+        let filter_bits = bits_from_bytes(&data.v_data);
+        let hasher = Hasher::new(data.n_hash_funcs, data.n_tweak, filter_bits.len());
+
+        Ok(BloomFilter {
+            filter_bits,
+            n_tweak: data.n_tweak,
+            n_flags: BloomFlags::from_masked(data.n_flags),
+            hasher,
+            pending_filteradd: None,
+            dirty: true,
+        })
+    }
+
+    /// Serialize the filter with bincode via [BloomFilterData], e.g. to be cached in a
+    /// key-value store
+    #[cfg(feature = "bincode")]
+    pub fn to_bincode(&self) -> Vec<u8> {
+        bincode::serialize(self).expect("filter is always serializable")
+    }
+
+    /// Deserialize the filter from bytes produced by [Self::to_bincode], rebuilding hash
+    /// functions.  [InvalidFilterData] returned if the bytes are malformed or the data
+    /// violates BIP-37 limits.
+    #[cfg(feature = "bincode")]
+    pub fn from_bincode(bytes: &[u8]) -> Result<Self, InvalidFilterData> {
+        let data: BloomFilterData =
+            bincode::deserialize(bytes).map_err(|_| InvalidFilterData::MalformedPayload)?;
+        data.try_into()
+    }
+
+    /// Format the filter as the hex string dashd's `getmerkleblocks` RPC accepts as its
+    /// filter argument, i.e. the hex encoded `filterload` payload with `vData` prefixed by
+    /// its `CompactSize` length.  Same as the [Display](fmt::Display) implementation.
+    pub fn to_rpc_hex(&self) -> String {
+        self.to_string()
+    }
+
+    /// Parse the filter from the hex string in the format of [Self::to_rpc_hex], same as
+    /// the [FromStr] implementation
+    pub fn from_rpc_hex(s: &str) -> Result<Self, InvalidFilterData> {
+        s.parse()
+    }
+
+    /// Serialize the filter as a payload of BIP-37 `filterload` P2P message.
+    pub fn to_filterload_bytes(&self) -> Vec<u8> {
+        let v_data = bits_to_bytes(&self.filter_bits);
+        let mut buf = Vec::with_capacity(v_data.len() + 18);
+
+        wire::write_compact_size(&mut buf, v_data.len() as u64);
+        buf.extend_from_slice(&v_data);
+        buf.extend_from_slice(&self.n_hash_funcs().to_le_bytes());
+        buf.extend_from_slice(&self.n_tweak.to_le_bytes());
+        buf.push(self.n_flags.into());
+
+        buf
+    }
+
+    /// Deserialize the filter from a payload of BIP-37 `filterload` P2P message.
+    /// [InvalidFilterData] returned if the payload is malformed, the filter violates
+    /// BIP-37 limits or is empty.  `nFlags` is interpreted like Dash Core does, see
+    /// [BloomFlags::from_masked].
+    pub fn from_filterload_bytes(bytes: &[u8]) -> Result<Self, InvalidFilterData> {
+        Self::read_filterload(bytes)?.try_into()
+    }
+
+    /// Deserialize the filter from a `filterload` payload like at
+    /// [Self::from_filterload_bytes], also accepting an empty filter like Dash Core does
+    /// for filters received from peers.  An empty filter matches everything and ignores
+    /// insertions.
+    pub fn from_filterload_bytes_lenient(bytes: &[u8]) -> Result<Self, InvalidFilterData> {
+        Self::from_data_allowing_empty(Self::read_filterload(bytes)?)
+    }
+
+    fn read_filterload(bytes: &[u8]) -> Result<BloomFilterData, InvalidFilterData> {
+        let mut reader = wire::Reader::new(bytes);
+
+        let v_data_len = reader
+            .read_compact_size()
+            .ok_or(InvalidFilterData::MalformedPayload)?;
+        if v_data_len > MAX_BLOOM_FILTER_SIZE as u64 {
+            return Err(InvalidFilterData::FilterTooLarge(
+                v_data_len.try_into().unwrap_or(usize::MAX),
+            ));
+        }
+
+        let v_data = reader
+            .read_bytes(v_data_len as usize)
+            .ok_or(InvalidFilterData::MalformedPayload)?
+            .to_vec();
+        let n_hash_funcs = reader
+            .read_u32_le()
+            .ok_or(InvalidFilterData::MalformedPayload)?;
+        let n_tweak = reader
+            .read_u32_le()
+            .ok_or(InvalidFilterData::MalformedPayload)?;
+        let n_flags = reader
+            .read_u8()
+            .ok_or(InvalidFilterData::MalformedPayload)?;
+
+        if !reader.is_empty() {
+            return Err(InvalidFilterData::MalformedPayload);
+        }
+
+        Ok(BloomFilterData {
+            v_data,
+            n_hash_funcs,
+            n_tweak,
+            n_flags: n_flags.into(),
+        })
+    }
+
+    /// Add element to Bloom filter, this has the same effect as BIP-37 `filteradd`.
+    /// Returns `false` if the element was already covered by the filter, meaning no
+    /// `filteradd` is needed to be sent to peers.
+    ///
+    /// With [Self::enable_filteradd_tracking], elements that changed the filter and fit
+    /// into [MAX_FILTERADD_SIZE] are kept until [Self::drain_filteradd_payloads] is
+    /// called.
+    pub fn insert(&mut self, element: &[u8]) -> bool {
+        let changed = self.hasher.insert(&mut self.filter_bits, element);
+        if changed {
+            self.dirty = true;
+            if let Some(pending) = &mut self.pending_filteradd {
+                if element.len() <= MAX_FILTERADD_SIZE {
+                    pending.push(element.to_vec());
+                }
+            }
+        }
+        changed
+    }
+
+    /// Add element given as the concatenation of `parts` like at [Self::insert], without
+    /// concatenating them unless the element is kept for `filteradd`
+    pub fn insert_parts(&mut self, parts: &[&[u8]]) -> bool {
+        let changed = self.hasher.insert_parts(&mut self.filter_bits, parts);
+        if changed {
+            self.dirty = true;
+            if let Some(pending) = &mut self.pending_filteradd {
+                let len: usize = parts.iter().map(|part| part.len()).sum();
+                if len <= MAX_FILTERADD_SIZE {
+                    pending.push(parts.concat());
+                }
+            }
+        }
+        changed
+    }
+
+    /// Start keeping elements inserted from now on for [Self::drain_filteradd_payloads].
+    /// Tracking is off by default, so inserting doesn't allocate for filters which are
+    /// never updated incrementally.
+    pub fn enable_filteradd_tracking(&mut self) {
+        self.pending_filteradd.get_or_insert_with(Vec::new);
+    }
+
+    /// Take payloads of BIP-37 `filteradd` messages for elements inserted since the
+    /// previous call or [Self::mark_clean], so peers holding an older copy of the filter
+    /// can be updated incrementally.  Always empty unless
+    /// [Self::enable_filteradd_tracking] was called.
+    ///
+    /// Elements larger than [MAX_FILTERADD_SIZE] are rejected by peers and reach them
+    /// only with the next `filterload`.
+    pub fn drain_filteradd_payloads(&mut self) -> Vec<Vec<u8>> {
+        self.pending_filteradd
+            .iter_mut()
+            .flat_map(|pending| pending.drain(..))
+            .map(|element| {
+                let mut buf = Vec::with_capacity(element.len() + 3);
+                wire::write_compact_size(&mut buf, element.len() as u64);
+                buf.extend_from_slice(&element);
+                buf
+            })
+            .collect()
+    }
+
+    /// Check if any bit changed since [Self::mark_clean] was called, meaning the filter
+    /// peers have loaded is outdated.  New filters are dirty as they weren't sent yet.
+    /// Outpoints inserted by [Self::matches_transaction_and_update] don't make the filter
+    /// dirty, as peers apply the same update.
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// Mark the filter as being in sync with peers, to be called after sending it with
+    /// `filterload`.  Elements pending for `filteradd` are discarded, as the sent filter
+    /// already includes them.
+    pub fn mark_clean(&mut self) {
+        self.dirty = false;
+        if let Some(pending) = &mut self.pending_filteradd {
+            pending.clear();
+        }
+    }
+
+    /// Get `nTweak` used in hash functions initialization
+    pub fn n_tweak(&self) -> u32 {
+        self.n_tweak
+    }
+
+    /// Get number of hash functions used
+    pub fn n_hash_funcs(&self) -> u32 {
+        self.hasher.hash_seeds.len() as u32
+    }
+
+    /// Get filter size in bytes
+    pub fn size_bytes(&self) -> usize {
+        self.filter_bits.len() / 8
+    }
+
+    /// Get filter size in bits
+    pub fn bit_len(&self) -> usize {
+        self.filter_bits.len()
+    }
+
+    /// Get false positives rate the filter has after `inserted` elements were added,
+    /// which can be used to decide whether the filter degraded enough to be rebuilt
+    pub fn effective_fp_rate(&self, inserted: u32) -> f64 {
+        false_positives_rate(self.n_hash_funcs(), self.bit_len(), inserted)
+    }
+
+    /// Get number of set bits
+    pub fn count_ones(&self) -> usize {
+        self.filter_bits.count_ones()
+    }
+
+    /// Check if no bits are set, so the filter matches nothing.  A filter with no bits
+    /// matches everything, so it is not empty.
+    pub fn is_empty(&self) -> bool {
+        self.bit_len() > 0 && self.filter_bits.not_any()
+    }
+
+    /// Check if all bits are set, so the filter matches everything.  This is also the
+    /// case for a filter with no bits.
+    pub fn is_full(&self) -> bool {
+        self.filter_bits.all()
+    }
+
+    /// Get fraction of set bits, a filter with no bits counts as full
+    pub fn fill_ratio(&self) -> f64 {
+        if self.bit_len() == 0 {
+            return 1.0;
+        }
+        self.count_ones() as f64 / self.bit_len() as f64
+    }
+
+    /// Estimate false positives rate from the fraction of set bits: `(X/m)^k`.  Unlike
+    /// [Self::effective_fp_rate] it doesn't require knowing how many elements were
+    /// inserted, e.g. for a filter received from a peer.
+    pub fn estimated_fp_rate(&self) -> f64 {
+        self.fill_ratio().powi(self.n_hash_funcs() as i32)
+    }
+
+    /// Estimate the number of inserted elements from the number of set bits:
+    /// `-(m/k) * ln(1 - X/m)`.  Infinity is returned for a filter with all bits set or
+    /// no bits.
+    pub fn estimated_element_count(&self) -> f64 {
+        if self.bit_len() == 0 {
+            return f64::INFINITY;
+        }
+        let m = self.bit_len() as f64;
+        let k = self.n_hash_funcs() as f64;
+        -(m / k) * (1.0 - self.fill_ratio()).ln()
+    }
+
+    /// Get filter update mode
+    pub fn flags(&self) -> BloomFlags {
+        self.n_flags
+    }
+
+    /// Get raw `nFlags` value as it is sent on the wire
+    pub fn n_flags(&self) -> u8 {
+        self.n_flags.into()
+    }
+
+    /// Add transaction outpoint to Bloom filter like at [Self::insert].  `txid` is
+    /// expected in internal byte order, i.e. reversed relative to its usual hex
+    /// representation.
+    ///
+    /// The outpoint is hashed in place and no memory is allocated, unless
+    /// [Self::enable_filteradd_tracking] was called and it changed the filter.
+    pub fn insert_outpoint(&mut self, txid: &[u8; 32], vout: u32) -> bool {
+        self.insert_parts(&[txid, &vout.to_le_bytes()])
+    }
+
+    /// Add public key and its HASH160 to Bloom filter like at [Self::insert], so both
+    /// pay-to-pubkey and pay-to-pubkey-hash outputs are matched
+    pub fn insert_pubkey(&mut self, pubkey: &[u8]) -> bool {
+        let key_changed = self.insert(pubkey);
+        let hash_changed = self.insert_pubkey_hash(&hashes::hash160(pubkey));
+        key_changed || hash_changed
+    }
+
+    /// Add public key hash to Bloom filter like at [Self::insert]
+    pub fn insert_pubkey_hash(&mut self, pubkey_hash: &[u8; 20]) -> bool {
+        self.insert(pubkey_hash)
+    }
+
+    /// Add hash160 of a Dash P2PKH or P2SH address to Bloom filter like at
+    /// [Self::insert]
+    #[cfg(feature = "addresses")]
+    pub fn insert_address(&mut self, address: &str) -> Result<bool, crate::InvalidAddress> {
+        let hash = crate::address::decode_hash160(address)?;
+        Ok(self.insert(&hash))
+    }
+
+    /// Add all non-empty data elements of the script to Bloom filter like at
+    /// [Self::insert], see [script::push_data]
+    pub fn insert_script_elements(&mut self, script: &[u8]) -> bool {
+        let mut changed = false;
+        for data in script::push_data(script).filter(|data| !data.is_empty()) {
+            changed |= self.insert(data);
+        }
+        changed
+    }
+
+    /// Unset all bits keeping filter parameters, so the filter matches nothing until
+    /// new elements are inserted
+    pub fn clear(&mut self) {
+        self.dirty |= self.filter_bits.any();
+        self.filter_bits.fill(false);
+        if let Some(pending) = &mut self.pending_filteradd {
+            pending.clear();
+        }
+    }
+
     /// Check if the filter possibly contains the item
     pub fn probably_contains(&self, item: &[u8]) -> bool {
-        let mut indexes = self.hasher.hash_indexes(item);
-        indexes.all(|i| {
-            self.filter_bits
-                .get(i)
-                .as_deref()
-                .copied()
-                .unwrap_or_default()
+        let words = self.filter_bits.as_raw_slice();
+        self.hasher.hash_indexes(item).all(|i| test_bit(words, i))
+    }
+
+    /// Check if the filter possibly contains the item given as the concatenation of
+    /// `parts`, without concatenating them
+    pub fn probably_contains_parts(&self, parts: &[&[u8]]) -> bool {
+        let words = self.filter_bits.as_raw_slice();
+        self.hasher
+            .hash_indexes_parts(parts)
+            .all(|i| test_bit(words, i))
+    }
+
+    /// Check if the filter possibly contains the transaction outpoint, see
+    /// [Self::insert_outpoint].  No memory is allocated.
+    pub fn probably_contains_outpoint(&self, txid: &[u8; 32], vout: u32) -> bool {
+        self.probably_contains_parts(&[txid, &vout.to_le_bytes()])
+    }
+
+    /// Check if the transaction is relevant to the filter as defined by BIP-37: its txid,
+    /// any data element of output scripts, any spent outpoint or any data element of
+    /// input scripts is matched by the filter.
+    pub fn matches_transaction(&self, tx: &Transaction) -> bool {
+        self.probably_contains(&tx.txid)
+            || tx
+                .outputs
+                .iter()
+                .any(|output| self.matches_script(&output.script_pubkey))
+            || self.matches_inputs(tx)
+    }
+
+    /// Check if the transaction is relevant to the filter like at
+    /// [Self::matches_transaction], also inserting outpoints of matched outputs
+    /// according to the filter's [BloomFlags], so transactions spending them will be
+    /// matched later on.
+    pub fn matches_transaction_and_update(&mut self, tx: &Transaction) -> bool {
+        let mut found = self.probably_contains(&tx.txid);
+
+        for (vout, output) in tx.outputs.iter().enumerate() {
+            let script_pubkey = &output.script_pubkey;
+            if !self.matches_script(script_pubkey) {
+                continue;
+            }
+            found = true;
+
+            let update = match self.n_flags {
+                BloomFlags::UpdateNone => false,
+                BloomFlags::UpdateAll => true,
+                BloomFlags::UpdateP2PubkeyOnly => {
+                    script::is_p2pk(script_pubkey) || script::is_multisig(script_pubkey)
+                }
+            };
+            // Peers apply the same update to their copy, so neither `filteradd` is needed
+            // nor the filter becomes dirty
+            if update {
+                self.hasher.insert_parts(
+                    &mut self.filter_bits,
+                    &[&tx.txid, &(vout as u32).to_le_bytes()],
+                );
+            }
+        }
+
+        found || self.matches_inputs(tx)
+    }
+
+    fn matches_inputs(&self, tx: &Transaction) -> bool {
+        tx.inputs.iter().any(|input| {
+            let outpoint = &input.previous_output;
+            self.probably_contains_outpoint(&outpoint.txid, outpoint.vout)
+                || self.matches_script(&input.script_sig)
         })
     }
+
+    fn matches_script(&self, script: &[u8]) -> bool {
+        script::push_data(script).any(|data| !data.is_empty() && self.probably_contains(data))
+    }
+
+    /// Check if the filter possibly contains all of the items, stops at the first miss
+    pub fn probably_contains_all<I>(&self, items: I) -> bool
+    where
+        I: IntoIterator,
+        I::Item: AsRef<[u8]>,
+    {
+        items
+            .into_iter()
+            .all(|item| self.probably_contains(item.as_ref()))
+    }
+
+    /// Check if the filter possibly contains any of the items, stops at the first match
+    pub fn probably_contains_any<I>(&self, items: I) -> bool
+    where
+        I: IntoIterator,
+        I::Item: AsRef<[u8]>,
+    {
+        items
+            .into_iter()
+            .any(|item| self.probably_contains(item.as_ref()))
+    }
+
+    /// Check many items at once, `i`-th bit of the result is set if the filter possibly
+    /// contains `i`-th item
+    pub fn probably_contains_batch<I>(&self, items: I) -> BitVec<u8>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<[u8]>,
+    {
+        items
+            .into_iter()
+            .map(|item| self.probably_contains(item.as_ref()))
+            .collect()
+    }
+
+    /// Check many items like at [Self::probably_contains_batch], splitting them across
+    /// the rayon thread pool
+    #[cfg(feature = "rayon")]
+    pub fn probably_contains_batch_par<T>(&self, items: &[T]) -> BitVec<u8>
+    where
+        T: AsRef<[u8]> + Sync,
+    {
+        use rayon::prelude::*;
+
+        let matches: Vec<bool> = items
+            .par_iter()
+            .map(|item| self.probably_contains(item.as_ref()))
+            .collect();
+        matches.into_iter().collect()
+    }
+
+    /// Check if the filters were built with the same size, `nTweak` and number of hash
+    /// functions, so they can be combined with [Self::union] or [Self::intersect]
+    pub fn compatible_with(&self, other: &BloomFilter) -> bool {
+        self.check_compatible(other).is_ok()
+    }
+
+    fn check_compatible(&self, other: &BloomFilter) -> Result<(), IncompatibleFilters> {
+        self.check_params(other.bit_len(), other.n_tweak, other.n_hash_funcs())
+    }
+
+    fn check_params(
+        &self,
+        bit_len: usize,
+        n_tweak: u32,
+        n_hash_funcs: u32,
+    ) -> Result<(), IncompatibleFilters> {
+        if self.bit_len() != bit_len {
+            return Err(IncompatibleFilters::BitLength(self.bit_len(), bit_len));
+        }
+        if self.n_tweak != n_tweak {
+            return Err(IncompatibleFilters::NTweak(self.n_tweak, n_tweak));
+        }
+        if self.n_hash_funcs() != n_hash_funcs {
+            return Err(IncompatibleFilters::NHashFuncs(
+                self.n_hash_funcs(),
+                n_hash_funcs,
+            ));
+        }
+        Ok(())
+    }
+
+    /// Hash the element once, so it can be inserted into or checked against any filter
+    /// compatible with this one, see [Self::compatible_with], without hashing it again
+    pub fn hash_element(&self, element: &[u8]) -> HashedElement {
+        HashedElement {
+            indexes: self.hasher.hash_indexes(element).collect(),
+            bit_len: self.bit_len(),
+            n_tweak: self.n_tweak,
+        }
+    }
+
+    fn check_hashed(&self, element: &HashedElement) -> Result<(), IncompatibleFilters> {
+        self.check_params(
+            element.bit_len,
+            element.n_tweak,
+            element.indexes.len() as u32,
+        )
+    }
+
+    /// Add element hashed with [Self::hash_element] like at [Self::insert].  The element
+    /// itself is not known, so it is not kept for `filteradd`.  [IncompatibleFilters]
+    /// returned if it was hashed for a filter with different parameters.
+    pub fn insert_hashed(&mut self, element: &HashedElement) -> Result<bool, IncompatibleFilters> {
+        self.check_hashed(element)?;
+        let mut changed = false;
+        for &i in &element.indexes {
+            changed |= !self.filter_bits.replace(i, true);
+        }
+        self.dirty |= changed;
+        Ok(changed)
+    }
+
+    /// Check if the filter possibly contains element hashed with [Self::hash_element].
+    /// [IncompatibleFilters] returned if it was hashed for a filter with different
+    /// parameters.
+    pub fn probably_contains_hashed(
+        &self,
+        element: &HashedElement,
+    ) -> Result<bool, IncompatibleFilters> {
+        self.check_hashed(element)?;
+        let words = self.filter_bits.as_raw_slice();
+        Ok(element.indexes.iter().all(|&i| test_bit(words, i)))
+    }
+
+    /// Merge two filters built with the same parameters, the result matches every
+    /// element either of them matches.  Update mode is taken from `self`.
+    pub fn union(&self, other: &BloomFilter) -> Result<BloomFilter, IncompatibleFilters> {
+        self.check_compatible(other)?;
+        let mut union = self.clone();
+        union.filter_bits |= &other.filter_bits;
+        Ok(union)
+    }
+
+    /// Intersect two filters built with the same parameters, the result only matches
+    /// elements both of them match.  False positives rate of the result is higher than
+    /// of a filter built from the common elements directly.  Update mode is taken from
+    /// `self`.
+    pub fn intersect(&self, other: &BloomFilter) -> Result<BloomFilter, IncompatibleFilters> {
+        self.check_compatible(other)?;
+        let mut intersection = self.clone();
+        intersection.filter_bits &= &other.filter_bits;
+        Ok(intersection)
+    }
+
+    /// Check if every bit set in `self` is also set in `other`, meaning `other` matches
+    /// everything `self` does, e.g. a newly loaded filter only extends the previous one
+    pub fn is_subset_of(&self, other: &BloomFilter) -> Result<bool, IncompatibleFilters> {
+        self.check_compatible(other)?;
+        Ok(self
+            .filter_bits
+            .as_raw_slice()
+            .iter()
+            .zip(other.filter_bits.as_raw_slice())
+            .all(|(ours, theirs)| ours & !theirs == 0))
+    }
+
+    /// Get Jaccard similarity of the filters' bit sets: number of bits set in both
+    /// divided by number of bits set in either.  Two empty filters are considered
+    /// identical.
+    pub fn jaccard_similarity(&self, other: &BloomFilter) -> Result<f64, IncompatibleFilters> {
+        let union = self.union(other)?.count_ones();
+        if union == 0 {
+            return Ok(1.0);
+        }
+        let intersection = self.intersect(other)?.count_ones();
+        Ok(intersection as f64 / union as f64)
+    }
+
+    /// Estimate the number of elements inserted into both filters from the estimated
+    /// element counts: `n(A) + n(B) - n(A ∪ B)`
+    pub fn estimated_intersection_count(
+        &self,
+        other: &BloomFilter,
+    ) -> Result<f64, IncompatibleFilters> {
+        let union = self.union(other)?;
+        Ok(
+            self.estimated_element_count() + other.estimated_element_count()
+                - union.estimated_element_count(),
+        )
+    }
+}
+
+/// Wipes the filter bits, `nTweak`, hash seeds and pending `filteradd` elements, as they
+/// reveal what the wallet is watching.
+///
+/// Wrappers retaining elements wipe them as well.  Filters shared between threads,
+/// [AtomicBloomFilter](crate::AtomicBloomFilter) and
+/// [ShardedBloomFilter](crate::ShardedBloomFilter), and the file-backed `MmapBloomFilter`
+/// are not wiped.
+///
+/// The `zeroize` feature adds [Drop] impls to the filter, [BloomFilterBuilder] and the
+/// wrappers retaining elements, so they are wiped when dropped.  Features are unified
+/// across the dependency graph, so the impls are present as soon as any crate enables the
+/// feature.  The types' fields are private and the types already own heap buffers, so
+/// the impls don't change what downstream code can do with them: values can be moved and
+/// dropped the same way with and without the feature.
+#[cfg(feature = "zeroize")]
+impl zeroize::Zeroize for BloomFilter {
+    fn zeroize(&mut self) {
+        self.filter_bits.as_raw_mut_slice().zeroize();
+        self.n_tweak.zeroize();
+        self.hasher.zeroize();
+        if let Some(pending) = &mut self.pending_filteradd {
+            pending.zeroize();
+        }
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl Drop for BloomFilter {
+    fn drop(&mut self) {
+        zeroize::Zeroize::zeroize(self);
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl zeroize::ZeroizeOnDrop for BloomFilter {}
+
+/// Serializes the filter as [BloomFilterData].
+#[cfg(feature = "borsh")]
+impl borsh::BorshSerialize for BloomFilter {
+    fn serialize<W: std::io::Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        bits_to_bytes(&self.filter_bits).serialize(writer)?;
+        self.n_hash_funcs().serialize(writer)?;
+        self.n_tweak.serialize(writer)?;
+        u32::from(self.n_flags()).serialize(writer)
+    }
+}
+
+/// Deserializes the filter from [BloomFilterData], failing with
+/// [std::io::ErrorKind::InvalidData] if it violates BIP-37 limits.
+#[cfg(feature = "borsh")]
+impl borsh::BorshDeserialize for BloomFilter {
+    fn deserialize_reader<R: std::io::Read>(reader: &mut R) -> std::io::Result<Self> {
+        let data = BloomFilterData::deserialize_reader(reader)?;
+        BloomFilter::from_data(data)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))
+    }
+}
+
+/// Formats the filter as hex encoded `filterload` payload.
+impl fmt::Display for BloomFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&hex::encode(&self.to_filterload_bytes()))
+    }
+}
+
+/// Parses the filter from hex encoded `filterload` payload.
+impl FromStr for BloomFilter {
+    type Err = InvalidFilterData;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bytes = hex::decode(s).ok_or(InvalidFilterData::InvalidHex)?;
+        Self::from_filterload_bytes(&bytes)
+    }
 }
@@ -0,0 +1,222 @@
+//! BIP-37 Bloom filter of fixed size without heap allocations.
+
+use std::f64::consts::LN_2;
+
+use crate::{
+    hasher, script, BadFilterParameters, BloomFilter, BloomFilterBuilder, BloomFilterData,
+    BloomFlags, MAX_BLOOM_FILTER_SIZE, MAX_HASH_FUNCS,
+};
+
+/// Size and number of hash functions of a [StaticBloomFilter], computed in const
+/// context the same way [BloomFilterBuilder::new](crate::BloomFilterBuilder::new) does,
+/// so the filter type can be derived from the expected number of elements and false
+/// positives rate, e.g. `StaticBloomFilter<{ PARAMS.size_bytes }, { PARAMS.n_hash_funcs }>`
+/// for a `const PARAMS: StaticFilterParams`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StaticFilterParams {
+    /// Filter size in bytes
+    pub size_bytes: usize,
+    /// Number of hash functions
+    pub n_hash_funcs: usize,
+}
+
+impl StaticFilterParams {
+    /// Compute parameters for `n_elements` with `false_positives_rate`.  Panics, failing
+    /// compilation if evaluated in const context, if there are no elements, the false
+    /// positives rate is not within `(0, 1)` or the filter would exceed BIP-37 limits.
+    pub const fn new(n_elements: u32, false_positives_rate: f64) -> Self {
+        assert!(
+            n_elements > 0,
+            "number of expected elements must be positive"
+        );
+        assert!(
+            false_positives_rate > 0.0 && false_positives_rate < 1.0,
+            "false positives rate is not within (0, 1)"
+        );
+
+        let size_bits = -1.0 / (LN_2 * LN_2) * n_elements as f64 * ln(false_positives_rate);
+        let size_bytes = size_bits as u64 / 8;
+        assert!(
+            size_bytes <= MAX_BLOOM_FILTER_SIZE as u64,
+            "filter size exceeds MAX_BLOOM_FILTER_SIZE"
+        );
+        let size_bytes = if size_bytes == 0 {
+            1
+        } else {
+            size_bytes as usize
+        };
+
+        let n_hash_funcs = ((size_bytes * 8) as f64 / n_elements as f64 * LN_2) as u32;
+        let n_hash_funcs = match n_hash_funcs {
+            0 => 1,
+            1..=MAX_HASH_FUNCS => n_hash_funcs as usize,
+            _ => MAX_HASH_FUNCS as usize,
+        };
+
+        StaticFilterParams {
+            size_bytes,
+            n_hash_funcs,
+        }
+    }
+}
+
+impl StaticFilterParams {
+    /// Compute parameters like [Self::new] with the false positives rate given in parts
+    /// per million using integer arithmetic only, see
+    /// [BloomFilterBuilder::params_ppm](crate::BloomFilterBuilder::params_ppm).  Panics on
+    /// the same inputs as [Self::new].
+    pub const fn new_ppm(n_elements: u32, false_positives_ppm: u32) -> Self {
+        match BloomFilterBuilder::params_ppm(n_elements, false_positives_ppm) {
+            Ok((size_bytes, n_hash_funcs)) => StaticFilterParams {
+                size_bytes,
+                n_hash_funcs: n_hash_funcs as usize,
+            },
+            Err(BadFilterParameters::ZeroElements) => {
+                panic!("number of expected elements must be positive")
+            }
+            Err(BadFilterParameters::InvalidFalsePositiveRate(_)) => {
+                panic!("false positives rate is not within 1..=999_999 ppm")
+            }
+            Err(_) => panic!("filter size exceeds MAX_BLOOM_FILTER_SIZE"),
+        }
+    }
+}
+
+/// Natural logarithm of a positive normal number.  `f64::ln` is not `const`, so the
+/// mantissa `m` is taken from the bits and `ln(m)` summed as `2 * atanh((m - 1) / (m + 1))`.
+const fn ln(x: f64) -> f64 {
+    let bits = x.to_bits();
+    let exponent = ((bits >> 52) & 0x7FF) as i64 - 1023;
+    let mantissa = f64::from_bits((bits & ((1 << 52) - 1)) | (1023 << 52));
+
+    let s = (mantissa - 1.0) / (mantissa + 1.0);
+    let s_squared = s * s;
+    let mut power = s;
+    let mut sum = 0.0;
+    let mut k = 1;
+    while k < 60 {
+        sum += power / k as f64;
+        power *= s_squared;
+        k += 2;
+    }
+
+    exponent as f64 * LN_2 + 2.0 * sum
+}
+
+/// BIP-37 Bloom filter of `BYTES` bytes with `K` hash functions stored inline, so it
+/// needs no allocator, e.g. to screen outputs against a watch set in hardware wallet
+/// firmware.  Hashes the same way as [BloomFilter], so it can be converted into one and
+/// sent to peers.
+///
+/// Sizes exceeding BIP-37 limits fail to compile.  [StaticFilterParams] computes sizes
+/// from the expected number of elements and false positives rate.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct StaticBloomFilter<const BYTES: usize, const K: usize> {
+    data: [u8; BYTES],
+    hash_seeds: [u32; K],
+    n_tweak: u32,
+    n_flags: BloomFlags,
+}
+
+impl<const BYTES: usize, const K: usize> StaticBloomFilter<BYTES, K> {
+    const VALID_SIZE: () = {
+        assert!(BYTES > 0, "filter size must be at least 1 byte");
+        assert!(
+            BYTES <= MAX_BLOOM_FILTER_SIZE,
+            "filter size exceeds MAX_BLOOM_FILTER_SIZE"
+        );
+        assert!(K > 0, "at least one hash function is required");
+        assert!(
+            K <= MAX_HASH_FUNCS as usize,
+            "hash functions exceed MAX_HASH_FUNCS"
+        );
+    };
+
+    /// Create empty filter
+    pub const fn new(n_tweak: u32, n_flags: BloomFlags) -> Self {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::VALID_SIZE;
+
+        let mut hash_seeds = [0; K];
+        let mut i = 0;
+        while i < K {
+            hash_seeds[i] = hasher::hash_seed(i as u32, n_tweak);
+            i += 1;
+        }
+
+        StaticBloomFilter {
+            data: [0; BYTES],
+            hash_seeds,
+            n_tweak,
+            n_flags,
+        }
+    }
+
+    fn bit_index(seed: u32, element: &[u8]) -> usize {
+        let hash = hasher::murmur3_32(element, seed);
+        hash as usize % (BYTES * 8)
+    }
+
+    /// Insert data element, returns `false` if it was already covered by the filter
+    pub fn insert(&mut self, element: &[u8]) -> bool {
+        let mut changed = false;
+        for seed in self.hash_seeds {
+            let i = Self::bit_index(seed, element);
+            let mask = 1 << (i % 8);
+            changed |= self.data[i / 8] & mask == 0;
+            self.data[i / 8] |= mask;
+        }
+        changed
+    }
+
+    /// Insert public key hash, matching P2PKH outputs paying to it
+    pub fn insert_pubkey_hash(&mut self, pubkey_hash: &[u8; 20]) -> bool {
+        self.insert(pubkey_hash)
+    }
+
+    /// Check if the filter probably contains the element
+    pub fn probably_contains(&self, element: &[u8]) -> bool {
+        self.hash_seeds.iter().all(|&seed| {
+            let i = Self::bit_index(seed, element);
+            self.data[i / 8] & (1 << (i % 8)) != 0
+        })
+    }
+
+    /// Check if any data element of the output script is probably in the filter
+    pub fn matches_script(&self, script_pubkey: &[u8]) -> bool {
+        script::push_data(script_pubkey)
+            .any(|data| !data.is_empty() && self.probably_contains(data))
+    }
+
+    /// Remove all elements
+    pub fn clear(&mut self) {
+        self.data = [0; BYTES];
+    }
+
+    /// Get the filter bytes in `filterload` bit order
+    pub fn as_bytes(&self) -> &[u8; BYTES] {
+        &self.data
+    }
+
+    /// Get `nTweak`
+    pub fn n_tweak(&self) -> u32 {
+        self.n_tweak
+    }
+
+    /// Get `nFlags`
+    pub fn flags(&self) -> BloomFlags {
+        self.n_flags
+    }
+}
+
+impl<const BYTES: usize, const K: usize> From<&StaticBloomFilter<BYTES, K>> for BloomFilter {
+    fn from(filter: &StaticBloomFilter<BYTES, K>) -> Self {
+        BloomFilter::from_data(BloomFilterData {
+            v_data: filter.data.to_vec(),
+            n_hash_funcs: K as u32,
+            n_tweak: filter.n_tweak,
+            n_flags: u8::from(filter.n_flags).into(),
+        })
+        .expect("sizes are checked at compile time")
+    }
+}
@@ -0,0 +1,55 @@
+//! BIP-37 `nFlags` values.
+
+/// Error type to indicate an unknown `nFlags` value.
+#[derive(Debug, thiserror::Error)]
+#[error("unknown nFlags value {0}")]
+pub struct UnknownBloomFlags(pub u8);
+
+/// Controls how matched outputs are added back to the filter on the node side.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[repr(u8)]
+pub enum BloomFlags {
+    /// `BLOOM_UPDATE_NONE`: the filter is never updated
+    #[default]
+    UpdateNone = 0,
+    /// `BLOOM_UPDATE_ALL`: outpoints of all matched outputs are added to the filter
+    UpdateAll = 1,
+    /// `BLOOM_UPDATE_P2PUBKEY_ONLY`: outpoints are added only for matched pay-to-pubkey
+    /// and multisig outputs
+    UpdateP2PubkeyOnly = 2,
+}
+
+/// Bits of `nFlags` selecting the update mode, `BLOOM_UPDATE_MASK` in Dash Core
+pub const BLOOM_UPDATE_MASK: u8 = 3;
+
+impl BloomFlags {
+    /// Interpret raw `nFlags` the way Dash Core does: bits outside [BLOOM_UPDATE_MASK]
+    /// are ignored and the unassigned value `3` doesn't update the filter, so it maps to
+    /// [BloomFlags::UpdateNone]
+    pub fn from_masked(n_flags: u32) -> Self {
+        match n_flags as u8 & BLOOM_UPDATE_MASK {
+            1 => BloomFlags::UpdateAll,
+            2 => BloomFlags::UpdateP2PubkeyOnly,
+            _ => BloomFlags::UpdateNone,
+        }
+    }
+}
+
+impl From<BloomFlags> for u8 {
+    fn from(flags: BloomFlags) -> Self {
+        flags as u8
+    }
+}
+
+impl TryFrom<u8> for BloomFlags {
+    type Error = UnknownBloomFlags;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(BloomFlags::UpdateNone),
+            1 => Ok(BloomFlags::UpdateAll),
+            2 => Ok(BloomFlags::UpdateP2PubkeyOnly),
+            _ => Err(UnknownBloomFlags(value)),
+        }
+    }
+}
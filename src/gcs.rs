@@ -0,0 +1,298 @@
+//! BIP-158 Golomb-coded set filters.
+//!
+//! Compact block filters are an alternative to BIP-37 for light clients: a node commits
+//! to a filter of each block's relevant scripts and clients test their watch set against
+//! it locally, without revealing it to the node.  BIP-157 filter headers chain filter
+//! hashes together, so filters received from peers can be validated against checkpoints.
+
+use std::hash::Hasher as _;
+
+use siphasher::sip::SipHasher24;
+
+use crate::{hashes, wire};
+
+/// Golomb-Rice coding parameter of the basic filter type
+pub const BASIC_FILTER_P: u8 = 19;
+
+/// False positive rate inverse of the basic filter type
+pub const BASIC_FILTER_M: u64 = 784_931;
+
+/// Error type to indicate a malformed serialized GCS filter.
+#[derive(Debug, thiserror::Error)]
+#[error("malformed GCS filter")]
+pub struct InvalidGcsFilter;
+
+/// Error type to indicate a filter header chain that doesn't lead to the expected
+/// header.
+#[derive(Debug, thiserror::Error)]
+#[error("filter header chain doesn't match the checkpoint")]
+pub struct FilterHeaderMismatch;
+
+/// Compute BIP-157 filter header from the filter hash and the previous filter header,
+/// which is all zeros for the genesis block
+pub fn filter_header(filter_hash: &[u8; 32], prev_header: &[u8; 32]) -> [u8; 32] {
+    let mut buf = [0; 64];
+    buf[..32].copy_from_slice(filter_hash);
+    buf[32..].copy_from_slice(prev_header);
+    hashes::sha256d(&buf)
+}
+
+/// Chain filter hashes of consecutive blocks starting after `prev_header`, returning
+/// the filter header of each block
+pub fn filter_headers<I>(prev_header: &[u8; 32], filter_hashes: I) -> Vec<[u8; 32]>
+where
+    I: IntoIterator<Item = [u8; 32]>,
+{
+    let mut prev_header = *prev_header;
+    filter_hashes
+        .into_iter()
+        .map(|filter_hash| {
+            prev_header = filter_header(&filter_hash, &prev_header);
+            prev_header
+        })
+        .collect()
+}
+
+/// Verify that chaining filter hashes of consecutive blocks starting after
+/// `prev_header` results in the `checkpoint` header
+pub fn verify_filter_headers<I>(
+    prev_header: &[u8; 32],
+    filter_hashes: I,
+    checkpoint: &[u8; 32],
+) -> Result<(), FilterHeaderMismatch>
+where
+    I: IntoIterator<Item = [u8; 32]>,
+{
+    let last_header = filter_hashes
+        .into_iter()
+        .fold(*prev_header, |prev_header, filter_hash| {
+            filter_header(&filter_hash, &prev_header)
+        });
+
+    if &last_header == checkpoint {
+        Ok(())
+    } else {
+        Err(FilterHeaderMismatch)
+    }
+}
+
+/// Golomb-coded set filter
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GcsFilter {
+    n: u32,
+    p: u8,
+    m: u64,
+    key: [u8; 16],
+    data: Vec<u8>,
+}
+
+impl GcsFilter {
+    /// Build a basic filter of the block from its elements (output scripts and scripts of
+    /// spent outputs, excluding `OP_RETURN` outputs).  `block_hash` is expected in
+    /// internal byte order.  Duplicate elements are included only once.
+    pub fn build_basic<I>(block_hash: &[u8; 32], elements: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: AsRef<[u8]>,
+    {
+        Self::build(block_hash, BASIC_FILTER_P, BASIC_FILTER_M, elements)
+    }
+
+    /// Build a filter with custom parameters
+    pub fn build<I>(block_hash: &[u8; 32], p: u8, m: u64, elements: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: AsRef<[u8]>,
+    {
+        let mut elements: Vec<Vec<u8>> =
+            elements.into_iter().map(|e| e.as_ref().to_vec()).collect();
+        elements.sort_unstable();
+        elements.dedup();
+
+        let mut filter = GcsFilter {
+            n: elements.len() as u32,
+            p,
+            m,
+            key: block_hash[..16].try_into().expect("32 bytes hash"),
+            data: Vec::new(),
+        };
+
+        let mut values: Vec<u64> = elements.iter().map(|e| filter.hash_to_range(e)).collect();
+        values.sort_unstable();
+
+        let mut writer = BitWriter::default();
+        let mut last = 0;
+        for value in values {
+            writer.write_golomb_rice(value - last, p);
+            last = value;
+        }
+        filter.data = writer.finish();
+
+        filter
+    }
+
+    /// Restore a basic filter of the block from its serialization
+    pub fn from_basic_bytes(block_hash: &[u8; 32], bytes: &[u8]) -> Result<Self, InvalidGcsFilter> {
+        Self::from_bytes(block_hash, BASIC_FILTER_P, BASIC_FILTER_M, bytes)
+    }
+
+    /// Restore a filter with custom parameters from its serialization
+    pub fn from_bytes(
+        block_hash: &[u8; 32],
+        p: u8,
+        m: u64,
+        bytes: &[u8],
+    ) -> Result<Self, InvalidGcsFilter> {
+        let mut reader = wire::Reader::new(bytes);
+        let n = reader
+            .read_compact_size()
+            .and_then(|n| u32::try_from(n).ok())
+            .ok_or(InvalidGcsFilter)?;
+
+        Ok(GcsFilter {
+            n,
+            p,
+            m,
+            key: block_hash[..16].try_into().expect("32 bytes hash"),
+            data: reader.read_remaining().to_vec(),
+        })
+    }
+
+    /// Serialize the filter as `CompactSize` number of elements followed by Golomb-Rice
+    /// coded bit stream
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(self.data.len() + 5);
+        wire::write_compact_size(&mut buf, self.n.into());
+        buf.extend_from_slice(&self.data);
+        buf
+    }
+
+    /// Double SHA256 of the serialized filter
+    pub fn filter_hash(&self) -> [u8; 32] {
+        hashes::sha256d(&self.to_bytes())
+    }
+
+    /// Compute BIP-157 filter header of the filter given the previous filter header
+    pub fn header(&self, prev_header: &[u8; 32]) -> [u8; 32] {
+        filter_header(&self.filter_hash(), prev_header)
+    }
+
+    /// Number of elements in the filter
+    pub fn len(&self) -> u32 {
+        self.n
+    }
+
+    /// Check if the filter has no elements
+    pub fn is_empty(&self) -> bool {
+        self.n == 0
+    }
+
+    /// Check if the filter possibly contains the element
+    pub fn contains(&self, element: &[u8]) -> bool {
+        self.match_any([element])
+    }
+
+    /// Check if the filter possibly contains any of the elements
+    pub fn match_any<I>(&self, elements: I) -> bool
+    where
+        I: IntoIterator,
+        I::Item: AsRef<[u8]>,
+    {
+        let mut queries: Vec<u64> = elements
+            .into_iter()
+            .map(|e| self.hash_to_range(e.as_ref()))
+            .collect();
+        if queries.is_empty() || self.n == 0 {
+            return false;
+        }
+        queries.sort_unstable();
+
+        let mut reader = BitReader::new(&self.data);
+        let mut queries = queries.into_iter().peekable();
+        let mut value = 0;
+        for _ in 0..self.n {
+            let Some(delta) = reader.read_golomb_rice(self.p) else {
+                return false;
+            };
+            value += delta;
+
+            while queries.next_if(|query| *query < value).is_some() {}
+            match queries.peek() {
+                Some(query) if *query == value => return true,
+                Some(_) => {}
+                None => return false,
+            }
+        }
+
+        false
+    }
+
+    fn hash_to_range(&self, element: &[u8]) -> u64 {
+        let f = u64::from(self.n) * self.m;
+        let mut hasher = SipHasher24::new_with_key(&self.key);
+        hasher.write(element);
+        ((u128::from(hasher.finish()) * u128::from(f)) >> 64) as u64
+    }
+}
+
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    n_bits: usize,
+}
+
+impl BitWriter {
+    fn write_bit(&mut self, bit: bool) {
+        if self.n_bits.is_multiple_of(8) {
+            self.bytes.push(0);
+        }
+        if bit {
+            *self.bytes.last_mut().expect("pushed above") |= 0x80 >> (self.n_bits % 8);
+        }
+        self.n_bits += 1;
+    }
+
+    fn write_golomb_rice(&mut self, value: u64, p: u8) {
+        for _ in 0..value >> p {
+            self.write_bit(true);
+        }
+        self.write_bit(false);
+        for i in (0..p).rev() {
+            self.write_bit(value >> i & 1 == 1);
+        }
+    }
+
+    fn finish(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+struct BitReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        BitReader { bytes, pos: 0 }
+    }
+
+    fn read_bit(&mut self) -> Option<bool> {
+        let byte = self.bytes.get(self.pos / 8)?;
+        let bit = byte & (0x80 >> (self.pos % 8)) != 0;
+        self.pos += 1;
+        Some(bit)
+    }
+
+    fn read_golomb_rice(&mut self, p: u8) -> Option<u64> {
+        let mut quotient = 0u64;
+        while self.read_bit()? {
+            quotient += 1;
+        }
+        let mut remainder = 0;
+        for _ in 0..p {
+            remainder = remainder << 1 | u64::from(self.read_bit()?);
+        }
+        Some(quotient << p | remainder)
+    }
+}
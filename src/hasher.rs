@@ -1,19 +1,203 @@
 //! Family of murmur3 hash functions.
 
-use std::io::Cursor;
+use bitvec::slice::BitSlice;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
 pub(crate) struct Hasher {
     pub(crate) filter_bits_len: usize,
     pub(crate) hash_seeds: Vec<u32>,
 }
 
+/// Wipes the seeds in place.  Their number and the filter size are kept, as otherwise
+/// [Hasher::index_seeds] would make the wiped filter match everything.
+#[cfg(feature = "zeroize")]
+impl zeroize::Zeroize for Hasher {
+    fn zeroize(&mut self) {
+        self.hash_seeds.as_mut_slice().zeroize();
+    }
+}
+
+const C1: u32 = 0xCC9E2D51;
+const C2: u32 = 0x1B873593;
+
+/// 32-bit MurmurHash3 of `data`, as used by BIP-37
+pub(crate) fn murmur3_32(data: &[u8], seed: u32) -> u32 {
+    let mut h1 = seed;
+
+    let mut blocks = data.chunks_exact(4);
+    for block in &mut blocks {
+        let k1 = u32::from_le_bytes([block[0], block[1], block[2], block[3]]);
+        h1 ^= mix_k1(k1);
+        h1 = h1.rotate_left(13).wrapping_mul(5).wrapping_add(0xE6546B64);
+    }
+
+    let tail = blocks.remainder();
+    if !tail.is_empty() {
+        let mut k1 = 0;
+        for (i, &byte) in tail.iter().enumerate() {
+            k1 |= (byte as u32) << (8 * i);
+        }
+        h1 ^= mix_k1(k1);
+    }
+
+    fmix32(h1 ^ data.len() as u32)
+}
+
+/// 32-bit MurmurHash3 of the concatenation of `parts`, without concatenating them
+pub(crate) fn murmur3_32_parts(parts: &[&[u8]], seed: u32) -> u32 {
+    let mut state = Murmur3::new(seed);
+    for part in parts {
+        state.update(part);
+    }
+    state.finish()
+}
+
+/// Streaming 32-bit MurmurHash3 state for data supplied in pieces
+#[derive(Debug, Clone)]
+pub(crate) struct Murmur3 {
+    h1: u32,
+    tail: [u8; 4],
+    tail_len: usize,
+    len: usize,
+}
+
+impl Murmur3 {
+    pub(crate) fn new(seed: u32) -> Self {
+        Murmur3 {
+            h1: seed,
+            tail: [0; 4],
+            tail_len: 0,
+            len: 0,
+        }
+    }
+
+    /// Feed next piece of data
+    pub(crate) fn update(&mut self, mut data: &[u8]) {
+        self.len += data.len();
+
+        if self.tail_len > 0 {
+            let n = data.len().min(4 - self.tail_len);
+            self.tail[self.tail_len..self.tail_len + n].copy_from_slice(&data[..n]);
+            self.tail_len += n;
+            data = &data[n..];
+            if self.tail_len < 4 {
+                return;
+            }
+            self.mix_block(u32::from_le_bytes(self.tail));
+            self.tail_len = 0;
+        }
+
+        let mut blocks = data.chunks_exact(4);
+        for block in &mut blocks {
+            self.mix_block(u32::from_le_bytes([block[0], block[1], block[2], block[3]]));
+        }
+        let rest = blocks.remainder();
+        self.tail[..rest.len()].copy_from_slice(rest);
+        self.tail_len = rest.len();
+    }
+
+    fn mix_block(&mut self, k1: u32) {
+        self.h1 ^= mix_k1(k1);
+        self.h1 = self
+            .h1
+            .rotate_left(13)
+            .wrapping_mul(5)
+            .wrapping_add(0xE6546B64);
+    }
+
+    /// Get hash of all data fed so far
+    pub(crate) fn finish(&self) -> u32 {
+        let mut h1 = self.h1;
+        if self.tail_len > 0 {
+            let mut k1 = 0;
+            for (i, &byte) in self.tail[..self.tail_len].iter().enumerate() {
+                k1 |= (byte as u32) << (8 * i);
+            }
+            h1 ^= mix_k1(k1);
+        }
+        fmix32(h1 ^ self.len as u32)
+    }
+}
+
+fn mix_k1(k1: u32) -> u32 {
+    k1.wrapping_mul(C1).rotate_left(15).wrapping_mul(C2)
+}
+
+fn fmix32(mut h: u32) -> u32 {
+    h ^= h >> 16;
+    h = h.wrapping_mul(0x85EBCA6B);
+    h ^= h >> 13;
+    h = h.wrapping_mul(0xC2B2AE35);
+    h ^ (h >> 16)
+}
+
+/// Seed of the `i`-th hash function as BIP-37 specifies: `i * 0xFBA4C795 + nTweak`
+pub(crate) const fn hash_seed(i: u32, n_tweak: u32) -> u32 {
+    i.wrapping_mul(0xFBA4C795).wrapping_add(n_tweak)
+}
+
 impl Hasher {
+    /// Create a family of `n_hash_funcs` hash functions seeded with [hash_seed]
+    pub(crate) fn new(n_hash_funcs: u32, n_tweak: u32, filter_bits_len: usize) -> Self {
+        let hash_seeds = (0..n_hash_funcs).map(|i| hash_seed(i, n_tweak)).collect();
+
+        Hasher {
+            filter_bits_len,
+            hash_seeds,
+        }
+    }
+
+    /// Apply multiple hash functions to input and return an iterator of raw hash values
+    pub(crate) fn hashes<'a>(&'a self, item: &'a [u8]) -> impl Iterator<Item = u32> + 'a {
+        self.hash_seeds
+            .iter()
+            .map(move |&seed| murmur3_32(item, seed))
+    }
+
+    /// Seeds to compute bit indexes with.  None for an empty filter, so it matches
+    /// everything and ignores insertions like in Dash Core (CVE-2013-5700).
+    fn index_seeds(&self) -> &[u32] {
+        if self.filter_bits_len == 0 {
+            &[]
+        } else {
+            &self.hash_seeds
+        }
+    }
+
     /// Apply multiple hash functions to input and return an iterator of hash results
     pub(crate) fn hash_indexes<'a>(&'a self, item: &'a [u8]) -> impl Iterator<Item = usize> + 'a {
-        self.hash_seeds.iter().map(move |seed| {
-            (murmur3::murmur3_32(&mut Cursor::new(item), *seed).expect("no IO happens") as usize)
-                % self.filter_bits_len
-        })
+        self.index_seeds()
+            .iter()
+            .map(move |&seed| murmur3_32(item, seed) as usize % self.filter_bits_len)
+    }
+
+    /// Apply multiple hash functions to input given as the concatenation of `parts` and
+    /// return an iterator of hash results
+    pub(crate) fn hash_indexes_parts<'a>(
+        &'a self,
+        parts: &'a [&'a [u8]],
+    ) -> impl Iterator<Item = usize> + 'a {
+        self.index_seeds()
+            .iter()
+            .map(move |&seed| murmur3_32_parts(parts, seed) as usize % self.filter_bits_len)
+    }
+
+    /// Set all bits the item hashes to, returns `true` if any of them was previously unset
+    pub(crate) fn insert(&self, filter_bits: &mut BitSlice<u64>, item: &[u8]) -> bool {
+        set_bits(filter_bits, self.hash_indexes(item))
+    }
+
+    /// Set all bits the item given as the concatenation of `parts` hashes to, like at
+    /// [Self::insert]
+    pub(crate) fn insert_parts(&self, filter_bits: &mut BitSlice<u64>, parts: &[&[u8]]) -> bool {
+        set_bits(filter_bits, self.hash_indexes_parts(parts))
+    }
+}
+
+fn set_bits(filter_bits: &mut BitSlice<u64>, indexes: impl Iterator<Item = usize>) -> bool {
+    let mut changed = false;
+    for i in indexes {
+        changed |= !filter_bits.replace(i, true);
     }
+    changed
 }
@@ -0,0 +1,22 @@
+//! Hash functions used by Dash.
+
+use hmac::{Hmac, Mac};
+use ripemd::Ripemd160;
+use sha2::{Digest, Sha256};
+
+/// `RIPEMD160(SHA256(data))`, used for public key and script hashes.
+pub(crate) fn hash160(data: &[u8]) -> [u8; 20] {
+    Ripemd160::digest(Sha256::digest(data)).into()
+}
+
+/// `SHA256(SHA256(data))`, used for transaction ids and merkle trees.
+pub(crate) fn sha256d(data: &[u8]) -> [u8; 32] {
+    Sha256::digest(Sha256::digest(data)).into()
+}
+
+/// `HMAC-SHA256(key, data)` as specified in RFC 2104.
+pub(crate) fn hmac_sha256(key: &[u8], data: &[u8]) -> [u8; 32] {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().into()
+}
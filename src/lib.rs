@@ -2,12 +2,92 @@
 
 #![deny(missing_docs)]
 
+#[cfg(feature = "addresses")]
+mod address;
+mod atomic;
 mod builder;
+mod counting;
+mod cuckoo;
+#[cfg(feature = "descriptors")]
+mod descriptor;
+#[cfg(feature = "ffi")]
+pub mod ffi;
 mod filter;
+mod fixed;
+mod flags;
+pub mod gcs;
 mod hasher;
+mod hashes;
+mod hex;
+pub mod local;
+pub mod merkleblock;
+#[cfg(feature = "mmap")]
+mod mmap;
+mod peer;
+mod persist;
+pub mod privacy;
+#[cfg(feature = "python")]
+mod python;
+mod rolling;
+mod scalable;
+mod scanner;
+pub mod script;
+mod session;
+mod sharded;
+#[cfg(feature = "arc-swap")]
+mod swap;
+mod transaction;
+#[cfg(feature = "uniffi")]
+mod uniffi;
+#[cfg(feature = "wasm")]
+mod wasm;
+mod watch;
+mod wire;
+mod xor;
+#[cfg(feature = "bip32")]
+mod xpub;
 
-pub use builder::{BadFilterParameters, BloomFilterBuilder};
-pub use filter::{BloomFilter, BloomFilterData};
+#[cfg(feature = "addresses")]
+pub use address::InvalidAddress;
+pub use atomic::AtomicBloomFilter;
+pub use builder::{BadFilterParameters, BloomFilterBuilder, CapacityExceeded};
+pub use counting::CountingBloomFilter;
+pub use cuckoo::{CuckooFilter, CuckooFilterFull};
+#[cfg(feature = "descriptors")]
+pub use descriptor::InvalidDescriptor;
+#[cfg(feature = "rkyv")]
+pub use filter::ArchivedBloomFilter;
+pub use filter::{
+    BloomFilter, BloomFilterData, HashedElement, IncompatibleFilters, InvalidFilterData,
+    MAX_BLOOM_FILTER_SIZE, MAX_FILTERADD_SIZE, MAX_HASH_FUNCS,
+};
+pub use fixed::{StaticBloomFilter, StaticFilterParams};
+pub use flags::{BloomFlags, UnknownBloomFlags, BLOOM_UPDATE_MASK};
+#[cfg(feature = "mmap")]
+pub use mmap::MmapBloomFilter;
+pub use peer::{InvalidFilterMessage, PeerFilterState};
+pub use persist::{PersistenceError, PERSISTENCE_VERSION};
+#[cfg(feature = "python")]
+pub use python::PyBloomFilter;
+pub use rolling::RollingBloomFilter;
+pub use scalable::ScalableBloomFilter;
+pub use scanner::{MatchReason, RelevanceScanner, TransactionMatch};
+pub use session::{FilterMessage, FilterSession};
+pub use sharded::ShardedBloomFilter;
+#[cfg(feature = "arc-swap")]
+pub use swap::SwappableBloomFilter;
+pub use transaction::{InvalidTransaction, OutPoint, Transaction, TxIn, TxOut};
+#[cfg(feature = "uniffi")]
+pub use uniffi::{UniffiBloomFilter, UniffiBloomFilterBuilder, UniffiFilterError};
+#[cfg(feature = "wasm")]
+pub use wasm::{WasmBloomFilter, WasmBloomFilterBuilder};
+pub use watch::{WatchFilter, WatchItem};
+pub use xor::XorFilter;
+#[cfg(feature = "bip32")]
+pub use xpub::InvalidXpub;
+
+#[cfg(feature = "uniffi")]
+::uniffi::setup_scaffolding!();
 
 #[cfg(test)]
 mod tests {
@@ -36,4 +116,2536 @@ mod tests {
         assert!(!filter.probably_contains(b"kek4"));
         assert!(!filter.probably_contains(b"kek5"));
     }
+
+    #[test]
+    fn all_and_any_queries() {
+        let filter = BloomFilter::builder_n_tweak(3, 0.001, 5)
+            .expect("parameters are correct")
+            .add_elements([b"kek1", b"kek2", b"kek3"])
+            .build();
+
+        assert!(filter.probably_contains_all([b"kek1", b"kek2", b"kek3"]));
+        assert!(!filter.probably_contains_all([b"kek1", b"kek4"]));
+        assert!(filter.probably_contains_all::<[&[u8]; 0]>([]));
+        assert!(filter.probably_contains_any([b"kek4", b"kek3"]));
+        assert!(!filter.probably_contains_any([b"kek4", b"kek5"]));
+        assert!(!filter.probably_contains_any::<[&[u8]; 0]>([]));
+    }
+
+    #[test]
+    fn batch_queries() {
+        let filter = BloomFilter::builder_n_tweak(3, 0.001, 5)
+            .expect("parameters are correct")
+            .add_elements([b"kek1", b"kek2", b"kek3"])
+            .build();
+
+        let batch = filter.probably_contains_batch([b"kek1", b"kek4", b"kek3"]);
+        assert_eq!(batch.len(), 3);
+        assert_eq!(batch.iter_ones().collect::<Vec<_>>(), [0, 2]);
+        assert!(filter.probably_contains_batch::<[&[u8]; 0]>([]).is_empty());
+    }
+
+    #[test]
+    fn invalid_inputs_are_rejected() {
+        assert_eq!(
+            BloomFilter::builder(0, 0.001).err(),
+            Some(BadFilterParameters::ZeroElements)
+        );
+        for rate in [0.0, 1.0, -0.5, 2.0, f64::INFINITY] {
+            assert_eq!(
+                BloomFilter::builder(10, rate).err(),
+                Some(BadFilterParameters::InvalidFalsePositiveRate(rate))
+            );
+        }
+        assert!(matches!(
+            BloomFilter::builder(10, f64::NAN),
+            Err(BadFilterParameters::InvalidFalsePositiveRate(rate)) if rate.is_nan()
+        ));
+    }
+
+    #[test]
+    fn degenerate_filters_are_avoided() {
+        let filter = BloomFilter::builder(1, 0.5)
+            .expect("parameters are correct")
+            .add_element(b"kek1")
+            .build();
+        assert_eq!(filter.size_bytes(), 1);
+        assert_eq!(filter.n_hash_funcs(), 5);
+        assert!(filter.probably_contains(b"kek1"));
+
+        assert_eq!(
+            BloomFilterBuilder::new_strict(1, 0.5, 0).err(),
+            Some(BadFilterParameters::EmptyFilter)
+        );
+    }
+
+    #[test]
+    fn core_compat_parameters() {
+        // Integer division of bits per element makes Dash Core use fewer hash functions
+        let builder =
+            BloomFilterBuilder::new_core_compat(10, 0.01, 0).expect("parameters are correct");
+        assert_eq!(builder.filter_size_bytes(), 11);
+        assert_eq!(builder.n_hash_funcs(), 5);
+
+        // Dash Core clamps the size instead of failing
+        let builder = BloomFilterBuilder::new_core_compat(100_000, 0.0001, 0)
+            .expect("parameters are correct");
+        assert_eq!(builder.filter_size_bytes(), MAX_BLOOM_FILTER_SIZE);
+        assert_eq!(builder.n_hash_funcs(), 1);
+
+        assert_eq!(
+            BloomFilterBuilder::new_core_compat(300_000, 0.001, 0).err(),
+            Some(BadFilterParameters::ZeroHashFunctions)
+        );
+        assert_eq!(
+            BloomFilterBuilder::new_core_compat(1, 0.5, 0).err(),
+            Some(BadFilterParameters::EmptyFilter)
+        );
+    }
+
+    #[test]
+    fn hash_functions_number_is_capped() {
+        let filter = BloomFilter::builder(1, 1e-30)
+            .expect("parameters are correct")
+            .build();
+        assert_eq!(filter.n_hash_funcs(), MAX_HASH_FUNCS);
+
+        assert_eq!(
+            BloomFilterBuilder::new_strict(1, 1e-30, 0).err(),
+            Some(BadFilterParameters::TooManyHashFunctions {
+                computed: 94,
+                max: MAX_HASH_FUNCS
+            })
+        );
+        assert!(BloomFilterBuilder::new_strict(3, 0.001, 0).is_ok());
+    }
+
+    #[test]
+    fn local_filters_are_unbounded() {
+        assert!(matches!(
+            BloomFilter::builder(300_000, 0.001),
+            Err(BadFilterParameters::FilterTooLarge {
+                computed: 539_159,
+                max: 36_000
+            })
+        ));
+
+        let filter = BloomFilterBuilder::new_local(300_000, 0.001, 0)
+            .expect("parameters are correct")
+            .add_element(b"kek1")
+            .build();
+        assert!(filter.size_bytes() > MAX_BLOOM_FILTER_SIZE);
+        assert!(filter.probably_contains(b"kek1"));
+    }
+
+    #[test]
+    fn builder_computed_parameters() {
+        let builder = BloomFilter::builder(10, 0.01).expect("parameters are correct");
+        assert_eq!(builder.filter_size_bytes(), 11);
+        assert_eq!(builder.n_hash_funcs(), 6);
+        // Rounding down to whole bytes makes the filter slightly worse than requested
+        assert!((builder.expected_fp_rate() - 0.0146).abs() < 0.0001);
+
+        let filter = builder.build();
+        assert_eq!(filter.effective_fp_rate(0), 0.0);
+        assert!((filter.effective_fp_rate(10) - 0.0146).abs() < 0.0001);
+        assert!(filter.effective_fp_rate(20) > 0.1);
+        assert_eq!(filter.estimated_fp_rate(), 0.0);
+
+        let filter = BloomFilter::builder(100, 0.01)
+            .expect("parameters are correct")
+            .add_elements((0u32..100).map(u32::to_le_bytes))
+            .build();
+        assert!((filter.estimated_fp_rate() - filter.effective_fp_rate(100)).abs() < 0.005);
+        assert!((filter.estimated_element_count() - 100.0).abs() < 10.0);
+
+        let filter = BloomFilter::builder(1, 0.01)
+            .expect("parameters are correct")
+            .add_element(b"kek1")
+            .build();
+        assert_eq!(filter.bit_len(), 8);
+        assert!((1..=7).contains(&filter.count_ones()));
+        assert_eq!(filter.fill_ratio(), filter.count_ones() as f64 / 8.0);
+
+        let full = BloomFilter::from_data(BloomFilterData {
+            v_data: vec![0xFF; 4],
+            n_hash_funcs: 3,
+            n_tweak: 0,
+            n_flags: 0,
+        })
+        .expect("data is valid");
+        assert!(full.is_full());
+        assert!(full.probably_contains(b"anything"));
+    }
+
+    #[test]
+    fn explicit_parameters() {
+        let filter = BloomFilter::builder_n_tweak(3, 0.001, 5)
+            .expect("parameters are correct")
+            .with_size_bytes(100)
+            .and_then(|b| b.with_hash_funcs(7))
+            .expect("parameters are correct")
+            .add_element(b"kek1")
+            .build();
+
+        assert_eq!(filter.size_bytes(), 100);
+        assert_eq!(filter.n_hash_funcs(), 7);
+        assert!(filter.probably_contains(b"kek1"));
+
+        let builder = || BloomFilter::builder(3, 0.001).expect("parameters are correct");
+        assert!(builder().with_size_bytes(0).is_err());
+        assert!(builder()
+            .with_size_bytes(MAX_BLOOM_FILTER_SIZE + 1)
+            .is_err());
+        assert!(builder().with_hash_funcs(0).is_err());
+        assert!(builder().with_hash_funcs(MAX_HASH_FUNCS + 1).is_err());
+    }
+
+    #[test]
+    fn capacity_tracking() {
+        let mut builder = BloomFilter::builder(2, 0.001)
+            .expect("parameters are correct")
+            .add_element(b"kek1");
+        assert_eq!(builder.elements_count(), 1);
+
+        assert!(builder.try_add_element(b"kek2").is_ok());
+        assert_eq!(
+            builder.try_add_element(b"kek3"),
+            Err(CapacityExceeded { capacity: 2 })
+        );
+        assert_eq!(builder.elements_count(), 2);
+
+        let filter = builder.build();
+        assert!(filter.probably_contains(b"kek2"));
+        assert!(!filter.probably_contains(b"kek3"));
+    }
+
+    #[test]
+    fn decoys_count() {
+        let builder = BloomFilter::builder(1000, 0.001)
+            .expect("parameters are correct")
+            .add_element(b"kek1");
+        let decoys = builder.decoys_for_fp_rate(0.05).expect("rate is valid");
+        assert!(decoys > 1000);
+        for rate in [0.0, -0.5, 1.0, 2.0, f64::NAN] {
+            assert!(matches!(
+                builder.decoys_for_fp_rate(rate),
+                Err(BadFilterParameters::InvalidFalsePositiveRate(_))
+            ));
+        }
+
+        let builder = builder.add_elements((0..decoys).map(u32::to_le_bytes));
+        assert_eq!(builder.decoys_for_fp_rate(0.05), Ok(0));
+        let filter = builder.build();
+        assert!((filter.effective_fp_rate(decoys + 1) - 0.05).abs() < 0.001);
+    }
+
+    #[cfg(feature = "rand")]
+    #[test]
+    fn random_tweak_and_decoys() {
+        use rand::SeedableRng;
+
+        let mut rng = rand::rngs::StdRng::seed_from_u64(1);
+        let builder = BloomFilterBuilder::new_random_tweak(1000, 0.001, &mut rng)
+            .expect("parameters are correct")
+            .add_element(b"kek1");
+        let decoys = builder.decoys_for_fp_rate(0.05).expect("rate is valid");
+        let builder = builder.add_decoys(decoys, &mut rng);
+        assert_eq!(builder.elements_count(), decoys + 1);
+
+        let filter = builder.build();
+        let other = BloomFilterBuilder::new_random_tweak(1000, 0.001, &mut rng)
+            .expect("parameters are correct")
+            .build();
+        assert_ne!(filter.n_tweak(), other.n_tweak());
+        assert!(filter.probably_contains(b"kek1"));
+        assert!((filter.estimated_fp_rate() - 0.05).abs() < 0.01);
+    }
+
+    #[test]
+    fn add_elements_from_iterator() {
+        let elements: Vec<Vec<u8>> = vec![b"kek1".to_vec(), b"kek2".to_vec()];
+
+        let mut builder = BloomFilter::builder_n_tweak(3, 0.001, 5)
+            .expect("parameters are correct")
+            .add_elements(&elements);
+        builder.extend([b"kek3"]);
+        let filter = builder.build();
+
+        assert!(filter.probably_contains(b"kek1"));
+        assert!(filter.probably_contains(b"kek2"));
+        assert!(filter.probably_contains(b"kek3"));
+        assert!(!filter.probably_contains(b"kek4"));
+    }
+
+    #[test]
+    fn insert_after_build() {
+        let mut filter = BloomFilter::builder_n_tweak(3, 0.001, 5)
+            .expect("parameters are correct")
+            .add_element(b"kek1")
+            .build();
+
+        assert!(!filter.probably_contains(b"kek2"));
+        assert!(filter.insert(b"kek2"));
+        assert!(!filter.insert(b"kek2"));
+
+        assert!(filter.probably_contains(b"kek1"));
+        assert!(filter.probably_contains(b"kek2"));
+
+        assert!(!filter.is_empty());
+        assert!(!filter.is_full());
+
+        filter.clear();
+        assert!(filter.is_empty());
+        assert!(!filter.probably_contains(b"kek1"));
+        assert!(!filter.probably_contains(b"kek2"));
+        assert_eq!(filter.n_tweak(), 5);
+    }
+
+    #[test]
+    fn filteradd_payloads() {
+        let mut filter = BloomFilter::builder_n_tweak(3, 0.001, 5)
+            .expect("parameters are correct")
+            .add_element(b"kek1")
+            .build();
+        filter.insert(b"kek2");
+        assert!(filter.drain_filteradd_payloads().is_empty());
+        filter.enable_filteradd_tracking();
+
+        filter.insert(b"kek1");
+        filter.insert(b"kek3");
+        filter.insert(&[0xAA; MAX_FILTERADD_SIZE + 1]);
+        assert_eq!(
+            filter.drain_filteradd_payloads(),
+            vec![b"\x04kek3".to_vec()]
+        );
+        assert!(filter.drain_filteradd_payloads().is_empty());
+
+        filter.insert(b"kek4");
+        filter.mark_clean();
+        assert!(filter.drain_filteradd_payloads().is_empty());
+
+        let before = filter.clone();
+        filter.insert(b"kek5");
+        let mut peer_copy = before;
+        assert_ne!(peer_copy, filter);
+        for payload in filter.drain_filteradd_payloads() {
+            peer_copy.insert(&payload[1..]);
+        }
+        assert_eq!(peer_copy, filter);
+    }
+
+    #[test]
+    fn dirty_state() {
+        let mut filter = BloomFilter::builder_n_tweak(3, 0.001, 5)
+            .expect("parameters are correct")
+            .build();
+        assert!(filter.is_dirty());
+        filter.mark_clean();
+        assert!(!filter.is_dirty());
+
+        filter.clear();
+        assert!(
+            !filter.is_dirty(),
+            "clearing an empty filter changes nothing"
+        );
+
+        assert!(filter.insert(b"kek1"));
+        assert!(filter.is_dirty());
+        filter.mark_clean();
+        assert!(!filter.is_dirty());
+
+        assert!(!filter.insert(b"kek1"));
+        assert!(
+            !filter.is_dirty(),
+            "covered elements don't change the filter"
+        );
+
+        filter.clear();
+        assert!(filter.is_dirty());
+
+        let mut filter = BloomFilter::builder_n_tweak(3, 0.001, 5)
+            .expect("parameters are correct")
+            .add_element(b"DDD")
+            .build_with_n_flags(BloomFlags::UpdateAll);
+        filter.mark_clean();
+        let tx = Transaction {
+            txid: [0x11; 32],
+            inputs: vec![],
+            outputs: vec![TxOut {
+                script_pubkey: vec![0x03, b'D', b'D', b'D'],
+            }],
+        };
+        assert!(filter.matches_transaction_and_update(&tx));
+        assert!(filter.probably_contains_outpoint(&[0x11; 32], 0));
+        assert!(
+            !filter.is_dirty(),
+            "peers apply the same update to their copy"
+        );
+    }
+
+    #[test]
+    fn filter_session() {
+        let mut session =
+            FilterSession::new(0.001, 5, BloomFlags::UpdateAll).expect("parameters are correct");
+        assert!(session.filter().is_none());
+
+        let Some(FilterMessage::FilterLoad(payload)) = session.add(b"kek1").unwrap() else {
+            panic!("first element loads the filter");
+        };
+        let loaded = BloomFilter::from_filterload_bytes(&payload).expect("payload is valid");
+        assert_eq!(Some(&loaded), session.filter());
+        assert_eq!(loaded.flags(), BloomFlags::UpdateAll);
+        assert!(!session.filter().unwrap().is_dirty());
+
+        assert_eq!(
+            session.add(b"kek2").unwrap(),
+            Some(FilterMessage::FilterAdd(b"\x04kek2".to_vec()))
+        );
+        assert!(!session.filter().unwrap().is_dirty());
+        assert_eq!(session.add(b"kek2").unwrap(), None);
+        assert!(matches!(
+            session.add(b"kek3").unwrap(),
+            Some(FilterMessage::FilterLoad(_))
+        ));
+
+        assert!(matches!(
+            session.remove(b"kek1").unwrap(),
+            Some(FilterMessage::FilterLoad(_))
+        ));
+        assert!(!session.filter().unwrap().probably_contains(b"kek1"));
+        assert_eq!(session.remove(b"kek1").unwrap(), None);
+
+        assert!(matches!(
+            session.rotate(6).unwrap(),
+            Some(FilterMessage::FilterLoad(_))
+        ));
+        assert_eq!(session.filter().unwrap().n_tweak(), 6);
+
+        session.remove(b"kek2").unwrap();
+        assert_eq!(
+            session.remove(b"kek3").unwrap(),
+            Some(FilterMessage::FilterClear)
+        );
+        assert!(session.is_empty());
+        assert!(session.filter().is_none());
+    }
+
+    #[test]
+    fn watch_filter_rebuilds() {
+        let mut watch =
+            WatchFilter::new(0.001, 5, BloomFlags::UpdateAll).expect("parameters are correct");
+        assert!(watch.take_rebuilt().is_some());
+        assert!(watch.take_rebuilt().is_none());
+        let initial_size = watch.filter().size_bytes();
+
+        assert_eq!(watch.watch(WatchItem::Element(b"kek1".to_vec())), Ok(true));
+        assert_eq!(watch.watch(WatchItem::Element(b"kek1".to_vec())), Ok(false));
+        assert!(!watch.is_rebuilt());
+        assert_eq!(watch.filter_mut().drain_filteradd_payloads().len(), 1);
+
+        for i in 0..100u32 {
+            watch
+                .watch(WatchItem::Outpoint(OutPoint {
+                    txid: [0x22; 32],
+                    vout: i,
+                }))
+                .expect("filter fits BIP-37 limits");
+        }
+        assert!(watch.is_rebuilt());
+        assert!(watch.filter().size_bytes() > initial_size);
+        assert!(watch.filter().effective_fp_rate(101) <= 0.001);
+        assert!(watch.filter().probably_contains(b"kek1"));
+        assert!(watch.filter().probably_contains_outpoint(&[0x22; 32], 99));
+
+        let payload = watch.take_rebuilt().expect("filter was rebuilt");
+        let loaded = BloomFilter::from_filterload_bytes(&payload).expect("payload is valid");
+        assert_eq!(&loaded, watch.filter());
+        assert_eq!(watch.len(), 101);
+    }
+
+    #[test]
+    fn filter_into_builder() {
+        let filter = BloomFilter::builder_n_tweak(10, 0.001, 5)
+            .expect("parameters are correct")
+            .add_element(b"kek1")
+            .build();
+
+        let builder = filter.clone().into_builder();
+        assert_eq!(builder.filter_size_bytes(), filter.size_bytes());
+        assert_eq!(builder.n_hash_funcs(), filter.n_hash_funcs());
+        assert_eq!(builder.elements_count(), 1);
+
+        let extended = builder
+            .add_element(b"kek2")
+            .build_with_n_flags(BloomFlags::UpdateAll);
+        assert!(extended.probably_contains(b"kek1"));
+        assert!(extended.probably_contains(b"kek2"));
+        assert_eq!(extended.n_tweak(), 5);
+        assert_eq!(extended.flags(), BloomFlags::UpdateAll);
+        assert_eq!(filter.is_subset_of(&extended), Ok(true));
+
+        let resumed = BloomFilterBuilder::from_data(extended.clone().into())
+            .expect("data is valid")
+            .add_element(b"kek3")
+            .build();
+        assert!(resumed.probably_contains(b"kek1"));
+        assert!(resumed.probably_contains(b"kek3"));
+        assert!(BloomFilterBuilder::from_data(BloomFilterData {
+            v_data: vec![],
+            n_hash_funcs: 1,
+            n_tweak: 0,
+            n_flags: 0,
+        })
+        .is_err());
+    }
+
+    #[test]
+    fn set_operations() {
+        let mut left = BloomFilter::builder_n_tweak(10, 0.001, 7)
+            .expect("parameters are correct")
+            .build();
+        let mut right = left.clone();
+        left.insert(b"kek1");
+        right.insert(b"kek2");
+
+        let union = left.union(&right).expect("filters are compatible");
+        assert!(union.probably_contains(b"kek1"));
+        assert!(union.probably_contains(b"kek2"));
+        assert!(!union.probably_contains(b"kek3"));
+
+        left.insert(b"kek3");
+        right.insert(b"kek3");
+        let intersection = left.intersect(&right).expect("filters are compatible");
+        assert!(intersection.probably_contains(b"kek3"));
+        assert!(!intersection.probably_contains(b"kek1"));
+        assert!(!intersection.probably_contains(b"kek2"));
+
+        assert_eq!(intersection.is_subset_of(&left), Ok(true));
+        let union = left.union(&right).expect("filters are compatible");
+        assert_eq!(left.is_subset_of(&union), Ok(true));
+        assert_eq!(union.is_subset_of(&left), Ok(false));
+
+        assert!(left.compatible_with(&right));
+        let other_tweak = BloomFilter::builder_n_tweak(10, 0.001, 8)
+            .expect("parameters are correct")
+            .build();
+        assert!(!left.compatible_with(&other_tweak));
+        assert_eq!(
+            left.union(&other_tweak).unwrap_err(),
+            IncompatibleFilters::NTweak(7, 8)
+        );
+        let other_size = BloomFilter::builder_n_tweak(100, 0.001, 7)
+            .expect("parameters are correct")
+            .build();
+        assert!(matches!(
+            left.union(&other_size),
+            Err(IncompatibleFilters::BitLength(_, _))
+        ));
+    }
+
+    #[test]
+    fn filter_similarity() {
+        let mut left = BloomFilter::builder_n_tweak(200, 0.001, 7)
+            .expect("parameters are correct")
+            .build();
+        let mut right = left.clone();
+        assert_eq!(left.jaccard_similarity(&right), Ok(1.0));
+        for i in 0..150u32 {
+            left.insert(&i.to_le_bytes());
+            right.insert(&(i + 100).to_le_bytes());
+        }
+        let similarity = left
+            .jaccard_similarity(&right)
+            .expect("filters are compatible");
+        assert!(similarity > 0.3 && similarity < 0.5);
+        let overlap = left
+            .estimated_intersection_count(&right)
+            .expect("filters are compatible");
+        assert!((overlap - 50.0).abs() < 10.0);
+    }
+
+    #[test]
+    fn outpoints() {
+        let txid = [0xAB; 32];
+        let mut outpoint = txid.to_vec();
+        outpoint.extend(7u32.to_le_bytes());
+
+        let mut filter = BloomFilter::builder(3, 0.001)
+            .expect("parameters are correct")
+            .add_outpoint(&txid, 7)
+            .build();
+
+        assert!(filter.probably_contains(&outpoint));
+        assert!(filter.probably_contains_outpoint(&txid, 7));
+        assert!(!filter.probably_contains_outpoint(&txid, 8));
+
+        assert!(filter.insert_outpoint(&txid, 8));
+        assert!(filter.probably_contains_outpoint(&txid, 8));
+    }
+
+    #[test]
+    fn raw_transaction_parsing() {
+        let mut raw = vec![0x01, 0x00, 0x00, 0x00, 0x01];
+        raw.extend([0x33; 32]);
+        raw.extend(1u32.to_le_bytes());
+        raw.extend([0x02, 0x51, 0x52]);
+        raw.extend(u32::MAX.to_le_bytes());
+        raw.push(0x01);
+        raw.extend(5000u64.to_le_bytes());
+        raw.extend([0x03, 0x4C, 0x01, 0x44]);
+        raw.extend(0u32.to_le_bytes());
+
+        let tx = Transaction::from_bytes(&raw).expect("transaction is valid");
+        assert_eq!(tx.txid, hashes::sha256d(&raw));
+        assert_eq!(
+            tx.inputs[0].previous_output,
+            OutPoint {
+                txid: [0x33; 32],
+                vout: 1
+            }
+        );
+        assert_eq!(tx.inputs[0].script_sig, [0x51, 0x52]);
+        assert_eq!(tx.outputs[0].script_pubkey, [0x4C, 0x01, 0x44]);
+
+        assert_eq!(
+            Transaction::from_bytes(&raw[..raw.len() - 1]).unwrap_err(),
+            InvalidTransaction
+        );
+
+        let mut special = raw.clone();
+        special[..4].copy_from_slice(&[0x03, 0x00, 0x05, 0x00]);
+        assert!(Transaction::from_bytes(&special).is_err());
+        special.extend([0x02, 0xAA, 0xBB]);
+        let tx = Transaction::from_bytes(&special).expect("transaction is valid");
+        assert_eq!(tx.txid, hashes::sha256d(&special));
+    }
+
+    #[test]
+    fn transaction_matching() {
+        let pubkey_hash = [0x11; 20];
+        let mut script_pubkey = vec![0x76, 0xA9, 0x14];
+        script_pubkey.extend(pubkey_hash);
+        script_pubkey.extend([0x88, 0xAC]);
+
+        let tx = Transaction {
+            txid: [0x22; 32],
+            inputs: vec![TxIn {
+                previous_output: OutPoint {
+                    txid: [0x33; 32],
+                    vout: 1,
+                },
+                script_sig: vec![0x4C, 0x03, 0x44, 0x44, 0x44],
+            }],
+            outputs: vec![TxOut { script_pubkey }],
+        };
+
+        let matches = |element: &[u8]| {
+            BloomFilter::builder(3, 0.001)
+                .expect("parameters are correct")
+                .add_element(element)
+                .build()
+                .matches_transaction(&tx)
+        };
+
+        assert!(matches(&[0x22; 32]));
+        assert!(matches(&pubkey_hash));
+        assert!(matches(&[0x44; 3]));
+        assert!(!matches(&[0x55; 20]));
+
+        let outpoint_filter = BloomFilter::builder(3, 0.001)
+            .expect("parameters are correct")
+            .add_outpoint(&[0x33; 32], 1)
+            .build();
+        assert!(outpoint_filter.matches_transaction(&tx));
+    }
+
+    #[test]
+    fn transaction_matching_updates_filter() {
+        let pubkey = [0x02; 33];
+        let mut p2pk_script = vec![0x21];
+        p2pk_script.extend(pubkey);
+        p2pk_script.push(0xAC);
+
+        let funding_tx = Transaction {
+            txid: [0x66; 32],
+            inputs: vec![],
+            outputs: vec![TxOut {
+                script_pubkey: p2pk_script,
+            }],
+        };
+        let spending_tx = Transaction {
+            txid: [0x77; 32],
+            inputs: vec![TxIn {
+                previous_output: OutPoint {
+                    txid: [0x66; 32],
+                    vout: 0,
+                },
+                script_sig: vec![],
+            }],
+            outputs: vec![],
+        };
+
+        for (n_flags, updated) in [
+            (BloomFlags::UpdateNone, false),
+            (BloomFlags::UpdateAll, true),
+            (BloomFlags::UpdateP2PubkeyOnly, true),
+        ] {
+            let mut filter = BloomFilter::builder(3, 0.001)
+                .expect("parameters are correct")
+                .add_element(&pubkey)
+                .build_with_n_flags(n_flags);
+
+            assert!(filter.matches_transaction_and_update(&funding_tx));
+            assert_eq!(filter.matches_transaction(&spending_tx), updated);
+        }
+    }
+
+    #[test]
+    fn peer_filter_state() {
+        let tx = Transaction {
+            txid: [0x22; 32],
+            inputs: vec![],
+            outputs: vec![TxOut {
+                script_pubkey: vec![0x4C, 0x03, 0x44, 0x44, 0x44],
+            }],
+        };
+
+        let mut peer = PeerFilterState::new();
+        assert!(peer.relays_transaction(&tx));
+        assert!(matches!(
+            peer.filteradd(b"\x03DDD"),
+            Err(InvalidFilterMessage::NoFilterLoaded)
+        ));
+
+        let filter = BloomFilter::builder(3, 0.001)
+            .expect("parameters are correct")
+            .build();
+        peer.filterload(&filter.to_filterload_bytes())
+            .expect("payload is valid");
+        assert!(!peer.relays_transaction(&tx));
+
+        peer.filteradd(b"\x03DDD").expect("payload is valid");
+        assert!(peer.relays_transaction(&tx));
+
+        let spend = Transaction {
+            txid: [0x66; 32],
+            inputs: vec![TxIn {
+                previous_output: OutPoint {
+                    txid: [0x22; 32],
+                    vout: 0,
+                },
+                script_sig: vec![],
+            }],
+            outputs: vec![],
+        };
+        assert!(!peer.relays_transaction(&spend));
+        assert!(peer.should_relay_transaction(&tx));
+        assert!(
+            !peer.relays_transaction(&spend),
+            "filter loaded with UpdateNone is not updated"
+        );
+
+        let filter = BloomFilter::builder(3, 0.001)
+            .expect("parameters are correct")
+            .add_element(b"DDD")
+            .build_with_n_flags(BloomFlags::UpdateAll);
+        peer.filterload(&filter.to_filterload_bytes())
+            .expect("payload is valid");
+        assert!(peer.should_relay_transaction(&tx));
+        assert!(peer.relays_transaction(&spend));
+
+        let mut oversized = vec![0xFD, 0x09, 0x02];
+        oversized.extend([0; 521]);
+        assert!(matches!(
+            peer.filteradd(&oversized),
+            Err(InvalidFilterMessage::ElementTooLarge(521))
+        ));
+        assert!(matches!(
+            peer.filteradd(b"\x03DD"),
+            Err(InvalidFilterMessage::MalformedPayload)
+        ));
+        assert!(matches!(
+            peer.filterload(&[0xFD, 0xFF, 0xFF]),
+            Err(InvalidFilterMessage::InvalidFilter(
+                InvalidFilterData::FilterTooLarge(65535)
+            ))
+        ));
+        assert!(peer.filter().is_some());
+
+        peer.filterclear();
+        assert!(peer.filter().is_none());
+        assert!(peer.relays_transaction(&tx));
+    }
+
+    #[test]
+    fn empty_filterload() {
+        let payload = [0x00, 0x03, 0, 0, 0, 0x05, 0, 0, 0, 0x01];
+        assert!(matches!(
+            BloomFilter::from_filterload_bytes(&payload),
+            Err(InvalidFilterData::EmptyFilter)
+        ));
+
+        let mut filter =
+            BloomFilter::from_filterload_bytes_lenient(&payload).expect("payload is valid");
+        assert!(filter.probably_contains(b"kek1"));
+        assert!(filter.probably_contains_outpoint(&[0x11; 32], 0));
+        assert!(!filter.insert(b"kek1"));
+        assert_eq!(filter.to_filterload_bytes(), payload);
+        assert!(!filter.is_empty());
+        assert!(filter.is_full());
+        assert_eq!(filter.fill_ratio(), 1.0);
+        assert_eq!(filter.estimated_fp_rate(), 1.0);
+        assert_eq!(filter.estimated_element_count(), f64::INFINITY);
+        assert!(matches!(
+            BloomFilter::from_filterload_bytes_lenient(&[0xFD, 0xFF, 0xFF]),
+            Err(InvalidFilterData::FilterTooLarge(65535))
+        ));
+
+        let tx = Transaction {
+            txid: [0x11; 32],
+            inputs: vec![],
+            outputs: vec![TxOut {
+                script_pubkey: vec![0x03, b'D', b'D', b'D'],
+            }],
+        };
+        let mut peer = PeerFilterState::new();
+        peer.filterload(&payload)
+            .expect("empty filters are accepted");
+        assert!(peer.relays_transaction(&tx));
+        assert!(peer.should_relay_transaction(&tx));
+    }
+
+    #[test]
+    fn relevance_scanner() {
+        let funding = Transaction {
+            txid: [0x22; 32],
+            inputs: vec![],
+            outputs: vec![
+                TxOut {
+                    script_pubkey: vec![0x03, 0x55, 0x55, 0x55],
+                },
+                TxOut {
+                    script_pubkey: vec![0x03, 0x44, 0x44, 0x44],
+                },
+            ],
+        };
+        let spend = Transaction {
+            txid: [0x66; 32],
+            inputs: vec![TxIn {
+                previous_output: OutPoint {
+                    txid: [0x22; 32],
+                    vout: 1,
+                },
+                script_sig: vec![],
+            }],
+            outputs: vec![],
+        };
+        let unrelated = Transaction {
+            txid: [0x77; 32],
+            inputs: vec![],
+            outputs: vec![],
+        };
+
+        let filter = BloomFilter::builder(3, 0.001)
+            .expect("parameters are correct")
+            .add_element(&[0x44; 3])
+            .build();
+        let mut scanner = RelevanceScanner::new(filter);
+
+        let matches = scanner.scan_all([&funding, &unrelated, &spend]);
+        assert_eq!(
+            matches,
+            vec![
+                TransactionMatch {
+                    txid: [0x22; 32],
+                    reasons: vec![MatchReason::OutputElement {
+                        vout: 1,
+                        element: vec![0x44; 3],
+                    }],
+                },
+                TransactionMatch {
+                    txid: [0x66; 32],
+                    reasons: vec![MatchReason::SpentOutpoint(OutPoint {
+                        txid: [0x22; 32],
+                        vout: 1,
+                    })],
+                },
+            ]
+        );
+        assert_eq!(scanner.discovered_outpoints().len(), 1);
+        assert!(!scanner.filter().matches_transaction(&spend));
+    }
+
+    #[test]
+    fn script_data_elements() {
+        let script = [
+            0x00, // OP_0
+            0x02, 0xAA, 0xBB, // push 2 bytes
+            0x76, // OP_DUP
+            0x4C, 0x01, 0xCC, // OP_PUSHDATA1
+            0x4D, 0x01, 0x00, 0xDD, // OP_PUSHDATA2
+            0x4E, 0x01, 0x00, 0x00, 0x00, 0xEE, // OP_PUSHDATA4
+            0x05, 0xFF, // truncated push
+            0x01, 0x00,
+        ];
+        let elements: Vec<&[u8]> = script::push_data(&script).collect();
+        assert_eq!(
+            elements,
+            [&[][..], &[0xAA, 0xBB], &[0xCC], &[0xDD], &[0xEE]]
+        );
+
+        let filter = BloomFilter::builder(5, 0.001)
+            .expect("parameters are correct")
+            .add_script_elements(&script)
+            .build();
+        assert!(filter.probably_contains(&[0xAA, 0xBB]));
+        assert!(filter.probably_contains(&[0xEE]));
+        assert!(!filter.probably_contains(&[]));
+    }
+
+    #[test]
+    fn pubkeys() {
+        // Compressed public key of private key 1
+        let pubkey =
+            hex::decode("0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798")
+                .expect("valid hex");
+        let pubkey_hash: [u8; 20] = hex::decode("751e76e8199196d454941c45d1b3a323f1433bd6")
+            .expect("valid hex")
+            .try_into()
+            .expect("20 bytes");
+
+        let filter = BloomFilter::builder(3, 0.001)
+            .expect("parameters are correct")
+            .add_pubkey(&pubkey)
+            .build();
+        assert!(filter.probably_contains(&pubkey));
+        assert!(filter.probably_contains(&pubkey_hash));
+
+        let mut filter = BloomFilter::builder(3, 0.001)
+            .expect("parameters are correct")
+            .build();
+        assert!(filter.insert_pubkey_hash(&pubkey_hash));
+        assert!(filter.probably_contains(&pubkey_hash));
+        assert!(!filter.probably_contains(&pubkey));
+        assert!(filter.insert_pubkey(&pubkey));
+        assert!(filter.probably_contains(&pubkey));
+    }
+
+    #[cfg(feature = "addresses")]
+    #[test]
+    fn addresses() {
+        let hash = [0x42; 20];
+        let address = |version: u8| {
+            bs58::encode([&[version][..], &hash].concat())
+                .with_check()
+                .into_string()
+        };
+        let p2pkh = address(76);
+        assert!(p2pkh.starts_with('X'));
+
+        let filter = BloomFilter::builder(3, 0.001)
+            .expect("parameters are correct")
+            .add_address(&p2pkh)
+            .expect("address is valid")
+            .build();
+        assert!(filter.probably_contains(&hash));
+
+        let mut filter = BloomFilter::builder(3, 0.001)
+            .expect("parameters are correct")
+            .build();
+        assert!(filter
+            .insert_address(&address(19))
+            .expect("address is valid"));
+        assert!(filter.probably_contains(&hash));
+
+        assert!(matches!(
+            filter.insert_address(&address(0)),
+            Err(InvalidAddress::UnknownVersion(0))
+        ));
+        assert!(matches!(
+            filter.insert_address("XkekInvalid"),
+            Err(InvalidAddress::Base58(_))
+        ));
+    }
+
+    #[cfg(feature = "bip32")]
+    #[test]
+    fn xpub_derivation() {
+        // BIP-32 test vector 1 master key
+        let xpub = "xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8";
+
+        let filter = BloomFilter::builder(8, 0.001)
+            .expect("parameters are correct")
+            .add_xpub(xpub, 5, 3)
+            .expect("xpub is valid")
+            .build();
+
+        let parsed: bip32::XPub = xpub.parse().expect("xpub is valid");
+        let pubkey_hash = |chain: u32, i: u32| {
+            let child = parsed
+                .derive_child(bip32::ChildNumber::new(chain, false).unwrap())
+                .and_then(|c| c.derive_child(bip32::ChildNumber::new(i, false).unwrap()))
+                .expect("derivable");
+            hashes::hash160(&child.to_bytes())
+        };
+
+        assert!(filter.probably_contains(&pubkey_hash(0, 0)));
+        assert!(filter.probably_contains(&pubkey_hash(0, 4)));
+        assert!(filter.probably_contains(&pubkey_hash(1, 2)));
+        assert!(!filter.probably_contains(&pubkey_hash(1, 3)));
+
+        assert!(BloomFilter::builder(8, 0.001)
+            .expect("parameters are correct")
+            .add_xpub("xpub-kek", 5, 3)
+            .is_err());
+    }
+
+    #[cfg(feature = "descriptors")]
+    #[test]
+    fn descriptors() {
+        let xpub = "xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8";
+        let parsed: bip32::XPub = xpub.parse().expect("xpub is valid");
+        let receive_pubkey = |i: u32| {
+            parsed
+                .derive_child(bip32::ChildNumber::new(0, false).unwrap())
+                .and_then(|c| c.derive_child(bip32::ChildNumber::new(i, false).unwrap()))
+                .expect("derivable")
+                .to_bytes()
+        };
+
+        let ranged = format!("pkh([d34db33f/44'/5'/0']{xpub}/0/*)#k9wt6s72");
+        let filter = BloomFilter::builder(10, 0.001)
+            .expect("parameters are correct")
+            .add_descriptor(&ranged, 0..5)
+            .expect("descriptor is valid")
+            .build();
+        assert!(filter.probably_contains(&receive_pubkey(0)));
+        assert!(filter.probably_contains(&hashes::hash160(&receive_pubkey(4))));
+        assert!(!filter.probably_contains(&hashes::hash160(&receive_pubkey(5))));
+
+        let (key1, key2) = (receive_pubkey(0), receive_pubkey(1));
+        let mut redeem_script = vec![0x51, 0x21];
+        redeem_script.extend(&key2);
+        redeem_script.push(0x21);
+        redeem_script.extend(&key1);
+        redeem_script.extend([0x52, 0xAE]);
+
+        let multisig = format!("sh(multi(1,{},{}))", hex::encode(&key2), hex::encode(&key1));
+        let filter = BloomFilter::builder(3, 0.001)
+            .expect("parameters are correct")
+            .add_descriptor(&multisig, 0..100)
+            .expect("descriptor is valid")
+            .build();
+        assert!(filter.probably_contains(&hashes::hash160(&redeem_script)));
+
+        let bad_checksum = format!("pkh({xpub}/0/*)#k9wt6s72");
+        assert!(matches!(
+            BloomFilter::builder(10, 0.001)
+                .expect("parameters are correct")
+                .add_descriptor(&bad_checksum, 0..5),
+            Err(InvalidDescriptor::Checksum)
+        ));
+    }
+
+    #[test]
+    fn merkleblock_construction() {
+        let tx = |i: u8| Transaction {
+            txid: [i; 32],
+            inputs: vec![],
+            outputs: vec![],
+        };
+        let transactions: Vec<_> = (1..=3).map(tx).collect();
+
+        let mut filter = BloomFilter::builder(3, 0.001)
+            .expect("parameters are correct")
+            .add_element(&[3; 32])
+            .build();
+        let (merkle_block, matched) =
+            merkleblock::MerkleBlock::from_block([0; 80], &transactions, &mut filter)
+                .expect("block is valid");
+        assert_eq!(matched, [2]);
+
+        let h12 = hashes::sha256d(&[[1; 32], [2; 32]].concat());
+        assert_eq!(merkle_block.txn.n_transactions, 3);
+        assert_eq!(merkle_block.txn.hashes, [h12, [3; 32]]);
+        assert_eq!(merkle_block.txn.flags, [true, false, true, true]);
+
+        let bytes = merkle_block.to_bytes();
+        assert_eq!(bytes.len(), 80 + 4 + 1 + 64 + 1 + 1);
+        assert_eq!(bytes[bytes.len() - 1], 0b1101);
+    }
+
+    #[test]
+    fn split_filters_for_privacy() {
+        let transactions: Vec<_> = (1..=20u8)
+            .map(|i| Transaction {
+                txid: [i; 32],
+                inputs: vec![],
+                outputs: vec![],
+            })
+            .collect();
+        let txids: Vec<_> = (1..=20u8).map(|i| [i; 32]).collect();
+
+        let filters = privacy::split_filters(
+            &txids,
+            std::num::NonZeroUsize::new(3).unwrap(),
+            0.0001,
+            5,
+            BloomFlags::UpdateNone,
+        )
+        .expect("parameters are correct");
+        assert_eq!(filters.len(), 3);
+        for txid in &txids {
+            let holders = filters
+                .iter()
+                .filter(|filter| filter.probably_contains(txid))
+                .count();
+            assert_eq!(holders, 1);
+        }
+
+        let per_filter = filters.into_iter().map(|mut filter| {
+            let (merkle_block, _) =
+                merkleblock::MerkleBlock::from_block([0; 80], &transactions, &mut filter)
+                    .expect("block is valid");
+            merkle_block.txn.extract_matches().expect("tree is valid").1
+        });
+        let matches = privacy::combine_matches(per_filter);
+        assert_eq!(matches.len(), 20);
+        assert!(matches.windows(2).all(|pair| pair[0].0 < pair[1].0));
+    }
+
+    #[test]
+    fn per_peer_tweaks() {
+        let elements = [b"kek1", b"kek2", b"kek3"];
+        let filters =
+            privacy::per_peer_filters(&elements, &[1, 2, 3], 0.001, BloomFlags::UpdateAll)
+                .expect("parameters are correct");
+
+        assert_eq!(filters.len(), 3);
+        for (filter, n_tweak) in filters.iter().zip(1..) {
+            assert_eq!(filter.n_tweak(), n_tweak);
+            assert!(filter.probably_contains_all(elements));
+        }
+        assert_ne!(filters[0].to_string(), filters[1].to_string());
+        assert!(!filters[0].compatible_with(&filters[1]));
+    }
+
+    #[test]
+    fn n_tweak_derivation() {
+        assert_eq!(
+            hashes::hmac_sha256(b"Jefe", b"what do ya want for nothing?"),
+            hex::decode("5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843")
+                .unwrap()
+                .as_slice()
+        );
+        assert_eq!(
+            hashes::hmac_sha256(
+                &[0xAA; 131],
+                b"Test Using Larger Than Block-Size Key - Hash Key First"
+            ),
+            hex::decode("60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54")
+                .unwrap()
+                .as_slice()
+        );
+
+        let seed = [0x42; 32];
+        let n_tweak = privacy::derive_n_tweak(&seed, 7);
+        assert_eq!(n_tweak, privacy::derive_n_tweak(&seed, 7));
+        assert_ne!(n_tweak, privacy::derive_n_tweak(&seed, 8));
+        assert_ne!(n_tweak, privacy::derive_n_tweak(&[0x43; 32], 7));
+    }
+
+    #[test]
+    fn anonymity_set_estimation() {
+        let filter = BloomFilter::builder(100, 0.001)
+            .expect("parameters are correct")
+            .build();
+        let fp_rate = filter.effective_fp_rate(100);
+
+        let anonymity = privacy::expected_anonymity_set(&filter, 100, 1_000_100);
+        assert!((anonymity - fp_rate * 10_000.0).abs() < 1e-9);
+        assert!(anonymity > 5.0);
+        assert!(privacy::expected_anonymity_set(&filter, 100, 10_000) < 1.0);
+        assert_eq!(privacy::expected_anonymity_set(&filter, 100, 50), 0.0);
+    }
+
+    #[test]
+    fn filter_rotation() {
+        let mut rotating = privacy::RotatingFilter::new(
+            [b"kek1", b"kek2"],
+            0.001,
+            1,
+            BloomFlags::UpdateNone,
+            privacy::RotationPolicy::Blocks(2),
+        )
+        .expect("parameters are correct");
+        assert!(rotating.insert(b"kek3"));
+        for _ in 0..100 {
+            assert!(!rotating.insert(b"kek3"));
+        }
+
+        rotating.record_block();
+        assert_eq!(rotating.rotate_if_due(2), Ok(None));
+        rotating.record_block();
+        let payload = rotating
+            .rotate_if_due(2)
+            .expect("parameters are correct")
+            .expect("rotation is due");
+        assert!(!rotating.rotation_due());
+
+        let loaded = BloomFilter::from_filterload_bytes(&payload).expect("payload is valid");
+        assert_eq!(loaded.n_tweak(), 2);
+        assert!(loaded.probably_contains_all([b"kek1", b"kek2", b"kek3"]));
+        let expected = BloomFilterBuilder::new_n_tweak(3, 0.001, 2)
+            .expect("parameters are correct")
+            .add_elements([b"kek1", b"kek2", b"kek3"])
+            .build();
+        assert_eq!(loaded, expected);
+
+        let mut by_time = privacy::RotatingFilter::new(
+            [b"kek1"],
+            0.001,
+            1,
+            BloomFlags::UpdateNone,
+            privacy::RotationPolicy::Elapsed(std::time::Duration::ZERO),
+        )
+        .expect("parameters are correct");
+        assert!(by_time.rotation_due());
+        by_time.record_query();
+        assert!(by_time.rotate(3).is_ok());
+    }
+
+    #[cfg(feature = "rand")]
+    #[test]
+    fn filter_rotation_with_decoys() {
+        use rand::SeedableRng;
+
+        let mut rng = rand::rngs::StdRng::seed_from_u64(1);
+        let mut rotating = privacy::RotatingFilter::new(
+            (0..100u32).map(u32::to_le_bytes),
+            0.001,
+            1,
+            BloomFlags::UpdateNone,
+            privacy::RotationPolicy::Queries(1),
+        )
+        .expect("parameters are correct")
+        .with_decoys(0.05, &mut rng)
+        .expect("rate is valid");
+        assert!(rotating.filter().estimated_fp_rate() > 0.01);
+        assert_eq!(rotating.filter().n_tweak(), 1);
+
+        let first = rotating
+            .rotate_with_rng(&mut rng)
+            .expect("parameters are correct");
+        let second = rotating
+            .rotate_with_rng(&mut rng)
+            .expect("parameters are correct");
+        assert_ne!(first, second);
+        assert!(rotating.filter().estimated_fp_rate() > 0.01);
+        assert!(rotating
+            .filter()
+            .probably_contains_all((0..100u32).map(u32::to_le_bytes)));
+
+        rotating.record_query();
+        let third = rotating
+            .rotate_if_due(7)
+            .expect("parameters are correct")
+            .expect("rotation is due");
+        assert_ne!(second, third);
+        assert_eq!(rotating.filter().n_tweak(), 7);
+        assert!(rotating.filter().estimated_fp_rate() > 0.01);
+
+        let rotating = privacy::RotatingFilter::new(
+            [b"kek1"],
+            0.001,
+            1,
+            BloomFlags::UpdateNone,
+            privacy::RotationPolicy::Queries(1),
+        )
+        .expect("parameters are correct");
+        assert!(matches!(
+            rotating.with_decoys(1.0, &mut rng),
+            Err(BadFilterParameters::InvalidFalsePositiveRate(_))
+        ));
+    }
+
+    #[test]
+    fn merkleblock_verification() {
+        let transactions: Vec<_> = (1..=5)
+            .map(|i| Transaction {
+                txid: [i; 32],
+                inputs: vec![],
+                outputs: vec![],
+            })
+            .collect();
+        let txids: Vec<_> = transactions.iter().map(|tx| tx.txid).collect();
+        let all_matched = merkleblock::PartialMerkleTree::from_txids(&txids, &[true; 5])
+            .expect("txids are valid");
+        let (root, _) = all_matched.extract_matches().expect("tree is valid");
+
+        let mut header = [0; 80];
+        header[36..68].copy_from_slice(&root);
+
+        let mut filter = BloomFilter::builder(3, 0.001)
+            .expect("parameters are correct")
+            .add_element(&[2; 32])
+            .add_element(&[5; 32])
+            .build();
+        let (merkle_block, _) =
+            merkleblock::MerkleBlock::from_block(header, &transactions, &mut filter)
+                .expect("block is valid");
+
+        let parsed = merkleblock::MerkleBlock::from_bytes(&merkle_block.to_bytes())
+            .expect("payload is valid");
+        assert_eq!(
+            parsed.verify().expect("proof is valid"),
+            [(1, [2; 32]), (4, [5; 32])]
+        );
+
+        let mut forged = parsed.clone();
+        forged.txn.hashes[0] = [0xFF; 32];
+        assert!(matches!(
+            forged.verify(),
+            Err(merkleblock::InvalidMerkleBlock::MerkleRootMismatch)
+        ));
+
+        let mut truncated = parsed;
+        truncated.txn.hashes.pop();
+        assert!(matches!(
+            truncated.verify(),
+            Err(merkleblock::InvalidMerkleBlock::InvalidTree(_))
+        ));
+    }
+
+    #[test]
+    fn merkleblock_invalid_transactions() {
+        use merkleblock::{InvalidBlockTransactions, MerkleBlock, PartialMerkleTree};
+
+        assert_eq!(
+            PartialMerkleTree::from_txids(&[], &[]),
+            Err(InvalidBlockTransactions::NoTransactions)
+        );
+        assert_eq!(
+            PartialMerkleTree::from_txids(&[[1; 32], [2; 32]], &[true]),
+            Err(InvalidBlockTransactions::MatchFlagsMismatch(1, 2))
+        );
+        let n_txids = merkleblock::MAX_BLOCK_TRANSACTIONS as usize + 1;
+        assert_eq!(
+            PartialMerkleTree::from_txids(&vec![[1; 32]; n_txids], &vec![false; n_txids]),
+            Err(InvalidBlockTransactions::TooManyTransactions(n_txids))
+        );
+
+        let mut filter = BloomFilter::builder(3, 0.001)
+            .expect("parameters are correct")
+            .build();
+        assert_eq!(
+            MerkleBlock::from_block([0; 80], &[], &mut filter),
+            Err(InvalidBlockTransactions::NoTransactions)
+        );
+    }
+
+    #[test]
+    fn merkleblock_oversized_tree() {
+        let tree = |n_transactions| merkleblock::PartialMerkleTree {
+            n_transactions,
+            hashes: vec![[1; 32]],
+            flags: vec![false],
+        };
+        assert_eq!(tree(u32::MAX).width(1), 1 << 31);
+        assert_eq!(tree(u32::MAX).width(32), 1);
+
+        for n_transactions in [merkleblock::MAX_BLOCK_TRANSACTIONS + 1, 1 << 31, u32::MAX] {
+            assert!(matches!(
+                tree(n_transactions).extract_matches(),
+                Err(merkleblock::InvalidMerkleBlock::InvalidTree(_))
+            ));
+        }
+        assert!(tree(merkleblock::MAX_BLOCK_TRANSACTIONS)
+            .extract_matches()
+            .is_ok());
+    }
+
+    #[test]
+    fn gcs_basic_filter() {
+        // BIP-158 test vector: testnet genesis block
+        let mut block_hash =
+            hex::decode("000000000933ea01ad0ee984209779baaec3ced90fa3f408719526f8d77f4943")
+                .expect("valid hex");
+        block_hash.reverse();
+        let block_hash: [u8; 32] = block_hash.try_into().expect("32 bytes");
+        let script = hex::decode("4104678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61deb649f6bc3f4cef38c4f35504e51ec112de5c384df7ba0b8d578a4c702b6bf11d5fac")
+            .expect("valid hex");
+
+        let filter = gcs::GcsFilter::build_basic(&block_hash, [&script]);
+        assert_eq!(hex::encode(&filter.to_bytes()), "019dfca8");
+        assert!(filter.contains(&script));
+        assert!(!filter.contains(b"kek"));
+
+        let mut header =
+            hex::decode("21584579b7eb08997773e5aeff3a7f932700042d0ed2a6129012b7d7ae81b750")
+                .expect("valid hex");
+        header.reverse();
+        assert_eq!(filter.header(&[0; 32]).to_vec(), header);
+
+        let restored = gcs::GcsFilter::from_basic_bytes(&block_hash, &filter.to_bytes())
+            .expect("filter is valid");
+        assert_eq!(restored, filter);
+
+        let elements: Vec<[u8; 4]> = (0u32..100).map(|i| i.to_le_bytes()).collect();
+        let filter = gcs::GcsFilter::build_basic(&block_hash, &elements);
+        assert_eq!(filter.len(), 100);
+        assert!(elements.iter().all(|e| filter.contains(e)));
+        assert!(filter.match_any([b"kek".as_slice(), &elements[42]]));
+        assert!(!filter.match_any([b"kek", b"lol"]));
+    }
+
+    #[test]
+    fn gcs_filter_headers() {
+        let filters: Vec<_> = (0u8..3)
+            .map(|i| gcs::GcsFilter::build_basic(&[i; 32], [[i; 25]]))
+            .collect();
+
+        let mut prev_header = [0; 32];
+        let expected: Vec<_> = filters
+            .iter()
+            .map(|filter| {
+                prev_header = filter.header(&prev_header);
+                prev_header
+            })
+            .collect();
+
+        let filter_hashes = filters.iter().map(gcs::GcsFilter::filter_hash);
+        assert_eq!(
+            gcs::filter_headers(&[0; 32], filter_hashes.clone()),
+            expected
+        );
+        assert!(gcs::verify_filter_headers(&[0; 32], filter_hashes.clone(), &expected[2]).is_ok());
+        assert!(gcs::verify_filter_headers(&[0; 32], filter_hashes, &expected[1]).is_err());
+    }
+
+    #[test]
+    fn rolling_filter_forgets_old_elements() {
+        let mut filter =
+            RollingBloomFilter::new(100, 0.000_001, 5).expect("parameters are correct");
+
+        for i in 0u32..100 {
+            filter.insert(&i.to_le_bytes());
+        }
+        assert!((0u32..100).all(|i| filter.probably_contains(&i.to_le_bytes())));
+
+        for i in 100u32..200 {
+            filter.insert(&i.to_le_bytes());
+        }
+        assert!((100u32..200).all(|i| filter.probably_contains(&i.to_le_bytes())));
+        assert!((0u32..50).all(|i| !filter.probably_contains(&i.to_le_bytes())));
+
+        filter.reset();
+        assert!(!filter.probably_contains(&150u32.to_le_bytes()));
+
+        assert!(RollingBloomFilter::new(0, 0.001, 0).is_err());
+        assert!(RollingBloomFilter::new(10, 1.5, 0).is_err());
+    }
+
+    #[test]
+    fn counting_filter_removal() {
+        let mut filter = CountingBloomFilter::new(3, 0.001, 5).expect("parameters are correct");
+        filter.insert(b"kek1");
+        filter.insert(b"kek2");
+        filter.insert(b"kek2");
+
+        assert!(filter.probably_contains(b"kek1"));
+        assert!(filter.remove(b"kek1"));
+        assert!(!filter.probably_contains(b"kek1"));
+        assert!(!filter.remove(b"kek1"));
+
+        assert!(filter.remove(b"kek2"));
+        assert!(filter.probably_contains(b"kek2"));
+
+        let exported = filter.to_bloom_filter(BloomFlags::UpdateNone);
+        assert!(exported.probably_contains(b"kek2"));
+        assert!(!exported.probably_contains(b"kek1"));
+    }
+
+    #[test]
+    fn scalable_filter_growth() {
+        let mut filter = ScalableBloomFilter::new(10, 0.001, 5).expect("parameters are correct");
+        for i in 0u32..100 {
+            assert!(filter.insert(&i.to_le_bytes()).expect("within limits"));
+        }
+        assert!(!filter.insert(&0u32.to_le_bytes()).expect("within limits"));
+
+        assert_eq!(filter.len(), 100);
+        assert_eq!(filter.n_layers(), 4);
+        assert!((0u32..100).all(|i| filter.probably_contains(&i.to_le_bytes())));
+
+        let exported = filter
+            .to_bloom_filter(BloomFlags::UpdateAll)
+            .expect("within limits");
+        assert!((0u32..100).all(|i| exported.probably_contains(&i.to_le_bytes())));
+        assert_eq!(exported.flags(), BloomFlags::UpdateAll);
+    }
+
+    #[test]
+    fn cuckoo_filter() {
+        let mut filter = CuckooFilter::new(1000, 5);
+        for i in 0u32..900 {
+            filter.insert(&i.to_le_bytes()).expect("not full");
+        }
+        assert_eq!(filter.len(), 900);
+        assert!((0u32..900).all(|i| filter.probably_contains(&i.to_le_bytes())));
+        let false_positives = (900u32..10_900)
+            .filter(|i| filter.probably_contains(&i.to_le_bytes()))
+            .count();
+        assert!(false_positives < 10);
+
+        for i in 0u32..450 {
+            assert!(filter.remove(&i.to_le_bytes()));
+        }
+        assert_eq!(filter.len(), 450);
+        assert!((450u32..900).all(|i| filter.probably_contains(&i.to_le_bytes())));
+
+        let mut tiny = CuckooFilter::new(4, 5);
+        let inserted = (0u32..100)
+            .take_while(|i| tiny.insert(&i.to_le_bytes()).is_ok())
+            .count();
+        assert!(inserted < 100);
+        assert!(matches!(tiny.insert(b"kek"), Err(CuckooFilterFull)));
+    }
+
+    #[test]
+    fn xor_filter() {
+        let elements: Vec<[u8; 4]> = (0u32..10_000).map(|i| i.to_le_bytes()).collect();
+        let filter = XorFilter::new(elements.iter().chain(&elements[..10]));
+
+        assert_eq!(filter.len(), 10_000);
+        assert!(filter.size_bytes() < 12_400);
+        assert!(elements.iter().all(|e| filter.probably_contains(e)));
+
+        let false_positives = (10_000u32..110_000)
+            .filter(|i| filter.probably_contains(&i.to_le_bytes()))
+            .count();
+        assert!(false_positives < 600);
+
+        assert!(!XorFilter::new::<[&[u8]; 0]>([]).probably_contains(b"kek"));
+    }
+
+    #[test]
+    fn blocked_filter() {
+        let mut filter =
+            local::BlockedBloomFilter::new(10_000, 0.01, 5).expect("parameters are correct");
+        for i in 0u32..10_000 {
+            filter.insert(&i.to_le_bytes());
+        }
+        assert!((0u32..10_000).all(|i| filter.probably_contains(&i.to_le_bytes())));
+
+        let false_positives = (10_000u32..110_000)
+            .filter(|i| filter.probably_contains(&i.to_le_bytes()))
+            .count();
+        assert!(false_positives < 1_500);
+
+        filter.clear();
+        assert!(!filter.probably_contains(&0u32.to_le_bytes()));
+    }
+
+    #[test]
+    fn filter_data_round_trip() {
+        let filter = BloomFilter::builder_n_tweak(3, 0.001, 5)
+            .expect("parameters are correct")
+            .add_element(b"kek1")
+            .add_element(b"kek2")
+            .build();
+
+        let data = BloomFilterData::from(filter);
+        let restored = BloomFilter::from_data(data.clone()).expect("data is valid");
+
+        assert!(restored.probably_contains(b"kek1"));
+        assert!(restored.probably_contains(b"kek2"));
+        assert!(!restored.probably_contains(b"kek3"));
+        assert_eq!(BloomFilterData::from(restored.clone()), data);
+
+        let mut other = restored.clone();
+        other.insert(b"kek3");
+        assert_ne!(other, restored);
+        let filters: std::collections::HashSet<_> = [restored.clone(), other, restored].into();
+        assert_eq!(filters.len(), 2);
+
+        let too_many_hashes = BloomFilterData {
+            n_hash_funcs: MAX_HASH_FUNCS + 1,
+            ..data
+        };
+        assert!(matches!(
+            BloomFilter::try_from(too_many_hashes),
+            Err(InvalidFilterData::TooManyHashFunctions(51))
+        ));
+    }
+
+    #[test]
+    fn filterload_serialization() {
+        let filter = BloomFilter::builder_n_tweak(10, 0.01, 0x01020304)
+            .expect("parameters are correct")
+            .build_with_n_flags(BloomFlags::UpdateAll);
+
+        assert_eq!(filter.size_bytes(), 11);
+        assert_eq!(filter.bit_len(), 88);
+        assert_eq!(filter.n_hash_funcs(), 6);
+        assert_eq!(filter.n_tweak(), 0x01020304);
+        assert_eq!(filter.n_flags(), 1);
+
+        let mut expected = vec![0x0b];
+        expected.extend([0; 11]);
+        expected.extend([0x06, 0x00, 0x00, 0x00]);
+        expected.extend([0x04, 0x03, 0x02, 0x01]);
+        expected.push(0x01);
+
+        assert_eq!(filter.to_filterload_bytes(), expected);
+    }
+
+    #[test]
+    fn filterload_round_trip() {
+        let filter = BloomFilter::builder_n_tweak(3, 0.001, 5)
+            .expect("parameters are correct")
+            .add_element(b"kek1")
+            .build_with_n_flags(BloomFlags::UpdateP2PubkeyOnly);
+
+        let bytes = filter.to_filterload_bytes();
+        let restored = BloomFilter::from_filterload_bytes(&bytes).expect("payload is valid");
+
+        assert_eq!(restored.to_filterload_bytes(), bytes);
+        assert!(restored.probably_contains(b"kek1"));
+        assert!(!restored.probably_contains(b"kek2"));
+        assert_eq!(restored.flags(), BloomFlags::UpdateP2PubkeyOnly);
+
+        let mut unknown_flags = bytes.clone();
+        for (n_flags, expected) in [
+            (3, BloomFlags::UpdateNone),
+            (0x05, BloomFlags::UpdateAll),
+            (0xFE, BloomFlags::UpdateP2PubkeyOnly),
+        ] {
+            *unknown_flags.last_mut().unwrap() = n_flags;
+            let restored =
+                BloomFilter::from_filterload_bytes(&unknown_flags).expect("flags are masked");
+            assert_eq!(restored.flags(), expected);
+        }
+
+        assert!(matches!(
+            BloomFilter::from_filterload_bytes(&bytes[..bytes.len() - 1]),
+            Err(InvalidFilterData::MalformedPayload)
+        ));
+        assert!(matches!(
+            BloomFilter::from_filterload_bytes(&[0xFD, 0x41, 0x8D]),
+            Err(InvalidFilterData::FilterTooLarge(36_161))
+        ));
+    }
+
+    #[test]
+    fn hex_round_trip() {
+        let filter = BloomFilter::builder_n_tweak(10, 0.01, 0x01020304)
+            .expect("parameters are correct")
+            .build_with_n_flags(BloomFlags::UpdateAll);
+
+        let hex = filter.to_string();
+        assert_eq!(hex, "0b0000000000000000000000060000000403020101");
+
+        let restored: BloomFilter = hex.to_uppercase().parse().expect("hex is valid");
+        assert_eq!(restored.to_filterload_bytes(), filter.to_filterload_bytes());
+
+        assert!(matches!(
+            "0b0".parse::<BloomFilter>(),
+            Err(InvalidFilterData::InvalidHex)
+        ));
+    }
+
+    #[test]
+    fn static_filter() {
+        const FILTER: StaticBloomFilter<3, 5> = StaticBloomFilter::new(0, BloomFlags::UpdateAll);
+
+        let mut filter = FILTER;
+        let elements = [
+            "99108ad8ed9bb6274d3980bab5a85c048f0950c8",
+            "b5a2c786d9ef4658287ced5914b37a1b4aa32eee",
+            "b9300670b4c5366e95b2699e8b18bc75e5f729c5",
+        ]
+        .map(|element| hex::decode(element).unwrap());
+        for element in &elements {
+            filter.insert(element);
+            assert!(filter.probably_contains(element));
+        }
+
+        // Dash Core's bloom_create_insert_serialize test vector
+        assert_eq!(filter.as_bytes(), &[0x61, 0x4E, 0x9B]);
+        assert_eq!(
+            BloomFilter::from(&filter).to_rpc_hex(),
+            "03614e9b050000000000000001"
+        );
+
+        let mut script = vec![0x76, 0xA9, 0x14];
+        script.extend(&elements[0]);
+        script.extend([0x88, 0xAC]);
+        assert!(filter.matches_script(&script));
+        assert!(!filter.matches_script(&[0x6A]));
+
+        filter.clear();
+        assert_eq!(filter, FILTER);
+    }
+
+    #[test]
+    fn static_filter_params() {
+        const PARAMS: StaticFilterParams = StaticFilterParams::new(20, 0.001);
+        type WalletFilter = StaticBloomFilter<{ PARAMS.size_bytes }, { PARAMS.n_hash_funcs }>;
+
+        for (n_elements, false_positives_rate) in
+            [(20, 0.001), (3, 0.01), (1, 0.5), (1000, 0.0001), (7, 0.3)]
+        {
+            let params = StaticFilterParams::new(n_elements, false_positives_rate);
+            let builder = BloomFilterBuilder::new(n_elements, false_positives_rate)
+                .expect("parameters are correct");
+            assert_eq!(params.size_bytes, builder.filter_size_bytes());
+            assert_eq!(params.n_hash_funcs, builder.n_hash_funcs() as usize);
+        }
+
+        let mut filter = WalletFilter::new(0, BloomFlags::UpdateNone);
+        assert!(filter.insert(b"element"));
+        assert_eq!(filter.as_bytes().len(), PARAMS.size_bytes);
+    }
+
+    #[test]
+    fn integer_params() {
+        const PARAMS: StaticFilterParams = StaticFilterParams::new_ppm(20, 1000);
+        assert_eq!(PARAMS, StaticFilterParams::new(20, 0.001));
+
+        for n_elements in [1, 3, 20, 1000, 5000] {
+            for ppm in [1, 100, 10_000, 500_000, 999_999] {
+                let params = BloomFilterBuilder::params_ppm(n_elements, ppm);
+                let Ok(builder) = BloomFilterBuilder::new(n_elements, ppm as f64 / 1e6) else {
+                    assert!(params.is_err());
+                    continue;
+                };
+                assert_eq!(
+                    params,
+                    Ok((builder.filter_size_bytes(), builder.n_hash_funcs()))
+                );
+            }
+        }
+
+        let builder = BloomFilterBuilder::new_ppm(3, 10_000, 0).expect("parameters are correct");
+        assert_eq!(builder.filter_size_bytes(), 3);
+        assert_eq!(builder.n_hash_funcs(), 5);
+
+        assert_eq!(
+            BloomFilterBuilder::params_ppm(0, 1000),
+            Err(BadFilterParameters::ZeroElements)
+        );
+        assert!(matches!(
+            BloomFilterBuilder::params_ppm(3, 1_000_000),
+            Err(BadFilterParameters::InvalidFalsePositiveRate(_))
+        ));
+        assert!(matches!(
+            BloomFilterBuilder::new_ppm(100_000, 1, 0),
+            Err(BadFilterParameters::FilterTooLarge { .. })
+        ));
+    }
+
+    #[test]
+    fn murmur3_vectors() {
+        // Dash Core's murmurhash3 test vectors
+        let vectors = [
+            (0x00000000, 0x00000000, ""),
+            (0x6A396F08, 0xFBA4C795, ""),
+            (0x81F16F39, 0xFFFFFFFF, ""),
+            (0x514E28B7, 0x00000000, "00"),
+            (0xEA3F0B17, 0xFBA4C795, "00"),
+            (0xFD6CF10D, 0x00000000, "ff"),
+            (0x16C6B7AB, 0x00000000, "0011"),
+            (0x8EB51C3D, 0x00000000, "001122"),
+            (0xB4471BF8, 0x00000000, "00112233"),
+            (0xE2301FA8, 0x00000000, "0011223344"),
+            (0xFC2E4A15, 0x00000000, "001122334455"),
+            (0xB074502C, 0x00000000, "00112233445566"),
+            (0x8034D2A0, 0x00000000, "0011223344556677"),
+            (0xB4698DEF, 0x00000000, "001122334455667788"),
+        ];
+        for (expected, seed, data) in vectors {
+            let data = hex::decode(data).unwrap();
+            assert_eq!(hasher::murmur3_32(&data, seed), expected);
+        }
+    }
+
+    #[test]
+    fn multi_part_hashing() {
+        let data: Vec<u8> = (0..23).collect();
+        for seed in [0, 0xFBA4C795] {
+            let expected = hasher::murmur3_32(&data, seed);
+            for first in 0..data.len() {
+                for second in first..data.len() {
+                    let parts = [&data[..first], &data[first..second], &data[second..]];
+                    assert_eq!(hasher::murmur3_32_parts(&parts, seed), expected);
+                }
+            }
+        }
+
+        let txid = [0x33; 32];
+        let vout = 7u32.to_le_bytes();
+        let mut filter = BloomFilterBuilder::new(10, 0.001)
+            .expect("parameters are correct")
+            .build();
+        filter.enable_filteradd_tracking();
+        assert!(filter.insert_parts(&[&txid, &vout]));
+        assert!(filter.probably_contains_outpoint(&txid, 7));
+        assert!(filter.probably_contains_parts(&[&txid[..16], &txid[16..], &vout]));
+        assert!(!filter.insert_parts(&[&txid, &vout]));
+
+        let mut expected = vec![36];
+        expected.extend(txid);
+        expected.extend(vout);
+        assert_eq!(filter.drain_filteradd_payloads(), [expected]);
+    }
+
+    #[test]
+    fn outpoint_hashing() {
+        let txid = [0x5A; 32];
+        let mut serialized = txid.to_vec();
+        serialized.extend(3u32.to_le_bytes());
+
+        let builder = || BloomFilterBuilder::new(10, 0.001).expect("parameters are correct");
+        let by_outpoint = builder().add_outpoint(&txid, 3).build();
+        let by_element = builder().add_element(&serialized).build();
+        assert_eq!(by_outpoint, by_element);
+        assert!(by_element.probably_contains_outpoint(&txid, 3));
+        assert!(!by_element.probably_contains_outpoint(&txid, 4));
+
+        let mut filter = builder().build();
+        filter.enable_filteradd_tracking();
+        assert!(filter.insert_outpoint(&txid, 3));
+        assert_eq!(filter, by_element);
+        assert_eq!(filter.drain_filteradd_payloads()[0][1..], serialized);
+    }
+
+    #[test]
+    fn word_storage_layout() {
+        let v_data: Vec<u8> = (1..=13).collect();
+        let filter = BloomFilter::from_data(BloomFilterData {
+            v_data: v_data.clone(),
+            n_hash_funcs: 3,
+            n_tweak: 7,
+            n_flags: 0,
+        })
+        .expect("data is valid");
+        assert_eq!(filter.size_bytes(), 13);
+        assert_eq!(filter.bit_len(), 104);
+        assert_eq!(
+            filter.count_ones(),
+            v_data
+                .iter()
+                .map(|b| b.count_ones() as usize)
+                .sum::<usize>()
+        );
+        assert_eq!(BloomFilterData::from(filter.clone()).v_data, v_data);
+        assert_eq!(filter.to_filterload_bytes()[1..14], v_data);
+
+        let mut filter = BloomFilterBuilder::new(5, 0.01)
+            .expect("parameters are correct")
+            .with_size_bytes(9)
+            .expect("size is valid")
+            .build();
+        for i in 0u32..20 {
+            filter.insert(&i.to_le_bytes());
+        }
+        let restored = BloomFilter::from_filterload_bytes(&filter.to_filterload_bytes())
+            .expect("payload is valid");
+        assert_eq!(restored, filter);
+        assert!((0u32..20).all(|i| restored.probably_contains(&i.to_le_bytes())));
+    }
+
+    #[test]
+    fn hashed_elements() {
+        let builder = |n_tweak| {
+            BloomFilterBuilder::new_n_tweak(10, 0.001, n_tweak).expect("parameters are correct")
+        };
+        let mut filters = [builder(5).build(), builder(5).build(), builder(5).build()];
+        let element = filters[0].hash_element(b"element");
+        assert_eq!(element.indexes().len(), filters[0].n_hash_funcs() as usize);
+
+        for filter in &mut filters[1..] {
+            assert_eq!(filter.insert_hashed(&element), Ok(true));
+            assert_eq!(filter.insert_hashed(&element), Ok(false));
+            assert!(filter.probably_contains(b"element"));
+            assert!(filter.is_dirty());
+        }
+        assert_eq!(filters[0].probably_contains_hashed(&element), Ok(false));
+        filters[0].insert(b"element");
+        assert_eq!(filters[0].probably_contains_hashed(&element), Ok(true));
+        assert_eq!(filters[1], filters[0]);
+        assert!(filters[1].drain_filteradd_payloads().is_empty());
+
+        let mut other = builder(6).build();
+        assert_eq!(
+            other.insert_hashed(&element),
+            Err(IncompatibleFilters::NTweak(6, 5))
+        );
+        assert!(other.is_empty());
+    }
+
+    #[test]
+    fn threaded_build() {
+        let elements: Vec<[u8; 4]> = (0u32..5000).map(u32::to_le_bytes).collect();
+        let builder = || BloomFilterBuilder::new(5000, 0.001).expect("parameters are correct");
+        let serial = builder().add_elements(&elements).build();
+
+        for n_threads in [1, 3, 8, 64] {
+            let threaded = builder()
+                .add_elements_threaded(&elements, std::num::NonZeroUsize::new(n_threads).unwrap());
+            assert_eq!(threaded.elements_count(), 5000);
+            assert_eq!(threaded.build(), serial);
+        }
+        let empty = builder().add_elements_threaded::<[u8; 4]>(&[], std::num::NonZeroUsize::MIN);
+        assert!(empty.build().is_empty());
+    }
+
+    #[test]
+    fn atomic_filter() {
+        let filter = AtomicBloomFilter::new(4000, 0.001, 3).expect("parameters are correct");
+        std::thread::scope(|scope| {
+            for thread in 0u32..4 {
+                let filter = &filter;
+                scope.spawn(move || {
+                    for i in thread * 1000..(thread + 1) * 1000 {
+                        filter.insert(&i.to_le_bytes());
+                    }
+                });
+            }
+        });
+        assert!((0u32..4000).all(|i| filter.probably_contains(&i.to_le_bytes())));
+
+        let exported = filter.to_bloom_filter(BloomFlags::UpdateAll);
+        let expected = BloomFilterBuilder::new_n_tweak(4000, 0.001, 3)
+            .expect("parameters are correct")
+            .add_elements((0u32..4000).map(u32::to_le_bytes))
+            .build_with_n_flags(BloomFlags::UpdateAll);
+        assert_eq!(exported, expected);
+        assert_eq!(
+            AtomicBloomFilter::from(expected).to_bloom_filter(BloomFlags::UpdateAll),
+            exported
+        );
+
+        assert!(!filter.insert(&0u32.to_le_bytes()));
+        filter.clear();
+        assert!(filter.to_bloom_filter(BloomFlags::UpdateNone).is_empty());
+    }
+
+    #[test]
+    fn sharded_filter() {
+        let n_shards = std::num::NonZeroUsize::new(4).unwrap();
+        let filter =
+            ShardedBloomFilter::new(4000, 0.001, 3, n_shards).expect("parameters are correct");
+        assert_eq!(filter.n_shards(), 4);
+        std::thread::scope(|scope| {
+            for thread in 0u32..4 {
+                let filter = &filter;
+                scope.spawn(move || {
+                    for i in thread * 1000..(thread + 1) * 1000 {
+                        filter.insert(&i.to_le_bytes());
+                    }
+                });
+            }
+        });
+        assert!((0u32..4000).all(|i| filter.probably_contains(&i.to_le_bytes())));
+
+        let expected = BloomFilterBuilder::new_n_tweak(4000, 0.001, 3)
+            .expect("parameters are correct")
+            .add_elements((0u32..4000).map(u32::to_le_bytes))
+            .build_with_n_flags(BloomFlags::UpdateAll);
+        assert_eq!(filter.to_bloom_filter(BloomFlags::UpdateAll), expected);
+        assert_eq!(filter.size_bytes(), 4 * expected.size_bytes());
+
+        let single = ShardedBloomFilter::new(4000, 0.001, 3, std::num::NonZeroUsize::MIN)
+            .expect("parameters are correct");
+        assert_eq!(single.size_bytes(), expected.size_bytes());
+    }
+
+    #[test]
+    fn rpc_hex() {
+        let filter = BloomFilterBuilder::new_core_compat(3, 0.01, 0)
+            .expect("parameters are correct")
+            .add_element(&hex::decode("99108ad8ed9bb6274d3980bab5a85c048f0950c8").unwrap())
+            .add_element(&hex::decode("b5a2c786d9ef4658287ced5914b37a1b4aa32eee").unwrap())
+            .add_element(&hex::decode("b9300670b4c5366e95b2699e8b18bc75e5f729c5").unwrap())
+            .build_with_n_flags(BloomFlags::UpdateAll);
+
+        // Dash Core's bloom_create_insert_serialize test vector
+        let rpc_hex = filter.to_rpc_hex();
+        assert_eq!(rpc_hex, "03614e9b050000000000000001");
+        assert_eq!(
+            BloomFilter::from_rpc_hex(&rpc_hex).expect("hex is valid"),
+            filter
+        );
+    }
+
+    #[cfg(feature = "cbor")]
+    #[test]
+    fn cbor_round_trip() {
+        let data = BloomFilterData {
+            v_data: vec![0xB5, 0x0F],
+            n_hash_funcs: 11,
+            n_tweak: 5,
+            n_flags: 1,
+        };
+
+        let cbor = data.to_cbor();
+        assert_eq!(
+            hex::encode(&cbor),
+            "a4646461746142b50f6a6e4861736846756e63730b666e547765616b05666e466c61677301"
+        );
+        assert_eq!(BloomFilterData::from_cbor(&cbor).expect("valid CBOR"), data);
+        assert!(matches!(
+            BloomFilterData::from_cbor(&cbor[..cbor.len() - 1]),
+            Err(InvalidFilterData::MalformedPayload)
+        ));
+    }
+
+    #[cfg(feature = "bincode")]
+    #[test]
+    fn bincode_round_trip() {
+        let mut filter = BloomFilter::builder_n_tweak(3, 0.001, 5)
+            .expect("parameters are correct")
+            .add_element(b"kek1")
+            .build_with_n_flags(BloomFlags::UpdateP2PubkeyOnly);
+
+        let mut restored = BloomFilter::from_bincode(&filter.to_bincode()).expect("valid bytes");
+        assert_eq!(restored, filter);
+        assert_eq!(restored.insert(b"kek2"), filter.insert(b"kek2"));
+        assert_eq!(restored, filter);
+
+        assert!(matches!(
+            BloomFilter::from_bincode(&[1, 2, 3]),
+            Err(InvalidFilterData::MalformedPayload)
+        ));
+        let too_large = bincode::serialize(&BloomFilterData {
+            v_data: vec![0; MAX_BLOOM_FILTER_SIZE + 1],
+            n_hash_funcs: 1,
+            n_tweak: 0,
+            n_flags: 0,
+        })
+        .expect("serializable");
+        assert!(matches!(
+            BloomFilter::from_bincode(&too_large),
+            Err(InvalidFilterData::FilterTooLarge(_))
+        ));
+    }
+
+    #[cfg(feature = "rkyv")]
+    #[test]
+    fn rkyv_archived_queries() {
+        let filter = BloomFilter::builder_n_tweak(3, 0.001, 5)
+            .expect("parameters are correct")
+            .add_element(b"kek1")
+            .add_element(b"kek2")
+            .build();
+
+        let bytes = rkyv::to_bytes::<rkyv::rancor::Error>(&BloomFilterData::from(filter.clone()))
+            .expect("serializable");
+        let archived = rkyv::access::<ArchivedBloomFilter, rkyv::rancor::Error>(&bytes)
+            .expect("valid archive");
+        assert!(archived.probably_contains(b"kek1"));
+        assert!(archived.probably_contains(b"kek2"));
+        assert!(!archived.probably_contains(b"kek3"));
+
+        let data = rkyv::deserialize::<BloomFilterData, rkyv::rancor::Error>(archived)
+            .expect("deserializable");
+        assert_eq!(BloomFilter::from_data(data).expect("data is valid"), filter);
+
+        let archive = |v_data: Vec<u8>, n_hash_funcs| {
+            rkyv::to_bytes::<rkyv::rancor::Error>(&BloomFilterData {
+                v_data,
+                n_hash_funcs,
+                n_tweak: 5,
+                n_flags: 0,
+            })
+            .expect("serializable")
+        };
+        let too_many_hashes = archive(vec![0xFF; 10], u32::MAX);
+        assert!(
+            rkyv::access::<ArchivedBloomFilter, rkyv::rancor::Error>(&too_many_hashes).is_err()
+        );
+        let too_large = archive(vec![0xFF; MAX_BLOOM_FILTER_SIZE + 1], 3);
+        assert!(rkyv::access::<ArchivedBloomFilter, rkyv::rancor::Error>(&too_large).is_err());
+
+        let empty = archive(vec![], 3);
+        let archived = rkyv::access::<ArchivedBloomFilter, rkyv::rancor::Error>(&empty)
+            .expect("valid archive");
+        assert!(archived.probably_contains(b"kek1"));
+    }
+
+    #[cfg(feature = "borsh")]
+    #[test]
+    fn borsh_round_trip() {
+        let filter = BloomFilter::builder_n_tweak(3, 0.001, 5)
+            .expect("parameters are correct")
+            .add_element(b"kek1")
+            .build_with_n_flags(BloomFlags::UpdateAll);
+
+        let bytes = borsh::to_vec(&filter).expect("serializable");
+        assert_eq!(bytes.len(), 4 + filter.size_bytes() + 4 + 4 + 4);
+        let restored: BloomFilter = borsh::from_slice(&bytes).expect("deserializable");
+        assert_eq!(restored, filter);
+
+        let data: BloomFilterData = borsh::from_slice(&bytes).expect("deserializable");
+        assert_eq!(data, BloomFilterData::from(filter));
+
+        let empty = borsh::to_vec(&BloomFilterData {
+            v_data: vec![],
+            n_hash_funcs: 1,
+            n_tweak: 0,
+            n_flags: 0,
+        })
+        .expect("serializable");
+        assert!(borsh::from_slice::<BloomFilter>(&empty).is_err());
+    }
+
+    #[cfg(feature = "zeroize")]
+    #[test]
+    fn zeroize_filters() {
+        use zeroize::Zeroize;
+
+        let mut builder = BloomFilter::builder_n_tweak(10, 0.001, 5)
+            .expect("parameters are correct")
+            .add_element(b"kek1");
+        builder.zeroize();
+        assert_eq!(builder.elements_count(), 0);
+        assert!(!builder.build().probably_contains(b"kek1"));
+
+        let mut filter = BloomFilter::builder_n_tweak(10, 0.001, 5)
+            .expect("parameters are correct")
+            .build();
+        filter.insert(b"kek1");
+        filter.zeroize();
+        assert!(filter.is_empty());
+        assert!(!filter.probably_contains(b"kek1"));
+        assert!(!filter.probably_contains(b"kek2"));
+        assert_eq!(filter.n_tweak(), 0);
+        assert!(filter.drain_filteradd_payloads().is_empty());
+
+        let mut rotating = privacy::RotatingFilter::new(
+            [b"kek1"],
+            0.001,
+            5,
+            BloomFlags::UpdateNone,
+            privacy::RotationPolicy::Blocks(1),
+        )
+        .expect("parameters are correct");
+        rotating.zeroize();
+        assert!(rotating.filter().is_empty());
+        assert!(!rotating.filter().probably_contains(b"kek1"));
+        assert!(rotating.insert(b"kek2"));
+        assert!(rotating.filter().probably_contains(b"kek2"));
+        rotating.rotate(6).expect("parameters are correct");
+        assert!(rotating.filter().probably_contains(b"kek2"));
+        assert!(!rotating.filter().probably_contains(b"kek1"));
+
+        let mut counting = CountingBloomFilter::new(10, 0.001, 5).expect("parameters are correct");
+        counting.insert(b"kek1");
+        counting.zeroize();
+        assert!(counting.to_bloom_filter(BloomFlags::UpdateNone).is_empty());
+        assert!(!counting.probably_contains(b"kek1"));
+
+        let mut scalable = ScalableBloomFilter::new(1, 0.001, 5).expect("parameters are correct");
+        scalable.insert(b"kek1").expect("parameters are correct");
+        scalable.insert(b"kek2").expect("parameters are correct");
+        scalable.zeroize();
+        assert!(scalable.is_empty());
+        assert!(scalable
+            .to_bloom_filter(BloomFlags::UpdateNone)
+            .expect("parameters are correct")
+            .is_empty());
+        assert!(!scalable.probably_contains(b"kek1"));
+        assert_eq!(scalable.n_layers(), 1);
+        assert_eq!(scalable.insert(b"kek3").ok(), Some(true));
+        assert!(scalable.probably_contains(b"kek3"));
+
+        let mut session =
+            FilterSession::new(0.001, 5, BloomFlags::UpdateAll).expect("parameters are correct");
+        session.add(b"kek1").expect("parameters are correct");
+        session.zeroize();
+        assert!(session.is_empty());
+        assert!(session.filter().is_none());
+        assert!(matches!(
+            session.add(b"kek2"),
+            Ok(Some(FilterMessage::FilterLoad(_)))
+        ));
+        let filter = session.filter().expect("filter is loaded");
+        assert!(filter.probably_contains(b"kek2"));
+        assert!(!filter.probably_contains(b"kek1"));
+
+        let mut watch =
+            WatchFilter::new(0.001, 5, BloomFlags::UpdateAll).expect("parameters are correct");
+        watch.watch(WatchItem::pubkey_hash(b"kek1")).unwrap();
+        let mut item = WatchItem::Element(b"kek1".to_vec());
+        watch.watch(item.clone()).unwrap();
+        watch.zeroize();
+        assert!(watch.is_empty());
+        assert!(watch.filter().is_empty());
+        assert!(!watch.filter().probably_contains(b"kek1"));
+        assert!(watch.filter_mut().drain_filteradd_payloads().is_empty());
+        assert!(watch.is_rebuilt());
+        assert_eq!(
+            watch.watch(WatchItem::Element(b"kek2".to_vec())).ok(),
+            Some(true)
+        );
+        let payload = watch.take_rebuilt().expect("filter is rebuilt");
+        let reloaded = BloomFilter::from_filterload_bytes(&payload).expect("payload is valid");
+        assert!(reloaded.probably_contains(b"kek2"));
+        item.zeroize();
+        assert_eq!(item, WatchItem::Element(vec![]));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let filter = BloomFilter::builder_n_tweak(3, 0.001, 5)
+            .expect("parameters are correct")
+            .add_element(b"kek1")
+            .build();
+
+        let json = serde_json::to_string(&filter).expect("serializable");
+        let restored: BloomFilter = serde_json::from_str(&json).expect("deserializable");
+
+        assert!(restored.probably_contains(b"kek1"));
+        assert_eq!(restored.to_filterload_bytes(), filter.to_filterload_bytes());
+
+        let json = serde_json::to_value(BloomFilterData {
+            v_data: vec![0xB5, 0x0F],
+            n_hash_funcs: 11,
+            n_tweak: 5,
+            n_flags: 1,
+        })
+        .expect("serializable");
+        assert_eq!(
+            json,
+            serde_json::json!({"data": "b50f", "nHashFuncs": 11, "nTweak": 5, "nFlags": 1})
+        );
+
+        let bytes = bincode::serialize(&filter).expect("serializable");
+        let v_data_len = filter.size_bytes();
+        assert_eq!(bytes.len(), 8 + v_data_len + 4 + 4 + 4);
+        let restored: BloomFilter = bincode::deserialize(&bytes).expect("deserializable");
+        assert_eq!(restored, filter);
+
+        let invalid = r#"{"data":"","nHashFuncs":1,"nTweak":0,"nFlags":0}"#;
+        assert!(serde_json::from_str::<BloomFilter>(invalid).is_err());
+        let invalid = r#"{"data":"zz","nHashFuncs":1,"nTweak":0,"nFlags":0}"#;
+        assert!(serde_json::from_str::<BloomFilter>(invalid).is_err());
+    }
+
+    #[cfg(feature = "wasm")]
+    #[test]
+    fn wasm_bindings() {
+        let mut builder = WasmBloomFilterBuilder::new(3, 0.01, 0).expect("parameters are valid");
+        for element in [
+            "99108ad8ed9bb6274d3980bab5a85c048f0950c8",
+            "b5a2c786d9ef4658287ced5914b37a1b4aa32eee",
+        ] {
+            builder
+                .add_element(&hex::decode(element).unwrap())
+                .expect("builder is not built");
+        }
+        builder
+            .add_outpoint(&[0x33; 32], 1)
+            .expect("txid is 32 bytes");
+        let mut filter = builder.build(1).expect("flags are valid");
+
+        assert!(filter.contains(&hex::decode("99108ad8ed9bb6274d3980bab5a85c048f0950c8").unwrap()));
+        assert!(!filter.contains(b"missing"));
+        assert!(filter.insert(b"added"));
+        assert!(filter.contains(b"added"));
+
+        let parsed = WasmBloomFilter::from_hex(&filter.to_hex()).expect("hex is valid");
+        assert_eq!(parsed.to_filterload(), filter.to_filterload());
+        let parsed =
+            WasmBloomFilter::from_filterload(&filter.to_filterload()).expect("payload is valid");
+        assert_eq!(parsed.to_hex(), filter.to_hex());
+    }
+
+    #[cfg(feature = "ffi")]
+    #[test]
+    fn ffi_round_trip() {
+        use ffi::*;
+
+        assert!(bip37_filter_new(3, 0.0, 0, 0).is_null());
+        assert!(bip37_filter_new(3, 0.01, 0, 3).is_null());
+
+        let element = hex::decode("99108ad8ed9bb6274d3980bab5a85c048f0950c8").unwrap();
+        unsafe {
+            let filter = bip37_filter_new(3, 0.01, 0, 1);
+            assert!(!filter.is_null());
+            assert!(bip37_filter_add_element(
+                filter,
+                element.as_ptr(),
+                element.len()
+            ));
+            assert!(bip37_filter_contains(
+                filter,
+                element.as_ptr(),
+                element.len()
+            ));
+            assert!(!bip37_filter_contains(filter, b"missing".as_ptr(), 7));
+
+            let mut len = 0;
+            let data = bip37_filter_serialize(filter, &mut len);
+            assert_eq!(
+                std::slice::from_raw_parts(data, len),
+                (*filter).to_filterload_bytes()
+            );
+
+            let parsed = bip37_filter_deserialize(data, len);
+            assert!(!parsed.is_null());
+            assert_eq!(*parsed, *filter);
+            assert!(bip37_filter_deserialize(data, len - 1).is_null());
+
+            bip37_filter_bytes_free(data, len);
+            bip37_filter_free(parsed);
+            bip37_filter_free(filter);
+            bip37_filter_free(std::ptr::null_mut());
+        }
+    }
+
+    #[cfg(feature = "uniffi")]
+    #[test]
+    fn uniffi_bindings() {
+        let element = hex::decode("99108ad8ed9bb6274d3980bab5a85c048f0950c8").unwrap();
+        let builder = UniffiBloomFilterBuilder::new(3, 0.01, 0).expect("parameters are valid");
+        builder
+            .add_element(element.clone())
+            .expect("builder is not built");
+        assert!(matches!(
+            builder.add_outpoint(vec![0x33; 20], 1),
+            Err(UniffiFilterError::InvalidTxid)
+        ));
+        assert!(matches!(
+            builder.build(3),
+            Err(UniffiFilterError::UnknownFlags(_))
+        ));
+
+        let filter = builder.build(1).expect("flags are valid");
+        assert!(matches!(
+            builder.add_element(element.clone()),
+            Err(UniffiFilterError::AlreadyBuilt)
+        ));
+        assert!(filter.contains(element));
+        assert!(filter.insert(b"added".to_vec()));
+        assert!(filter.contains(b"added".to_vec()));
+
+        let parsed = UniffiBloomFilter::from_hex(filter.to_hex()).expect("hex is valid");
+        assert_eq!(parsed.to_filterload(), filter.to_filterload());
+        assert!(UniffiBloomFilter::from_filterload(vec![0x01]).is_err());
+    }
+
+    #[cfg(feature = "python")]
+    #[test]
+    fn python_bindings() {
+        use pyo3::{types::PyBytesMethods, Python};
+
+        assert!(PyBloomFilter::new(3, 0.0, 0, 0).is_err());
+        let mut filter = PyBloomFilter::new(3, 0.01, 0, 1).expect("parameters are valid");
+        assert!(filter.insert(b"element"));
+        assert!(filter.contains(b"element"));
+        assert!(filter.insert_outpoint(&[0x33; 20], 1).is_err());
+
+        let mut raw = vec![0x01, 0x00, 0x00, 0x00, 0x01];
+        raw.extend([0x33; 32]);
+        raw.extend(1u32.to_le_bytes());
+        raw.extend([0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0x01]);
+        raw.extend(5000u64.to_le_bytes());
+        raw.extend([0x03, 0x4C, 0x01, 0x44]);
+        raw.extend(0u32.to_le_bytes());
+        assert!(!filter
+            .matches_transaction(&raw)
+            .expect("transaction is valid"));
+        assert!(filter
+            .insert_outpoint(&[0x33; 32], 1)
+            .expect("txid is 32 bytes"));
+        assert!(filter
+            .matches_transaction(&raw)
+            .expect("transaction is valid"));
+        assert!(filter.matches_transaction(&raw[1..]).is_err());
+
+        let parsed = PyBloomFilter::from_hex(&filter.to_hex()).expect("hex is valid");
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let bytes = filter.to_filterload(py);
+            assert_eq!(bytes.as_bytes(), parsed.to_filterload(py).as_bytes());
+            assert!(PyBloomFilter::from_filterload(bytes.as_bytes()).is_ok());
+        });
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn parallel_insertion() {
+        let elements: Vec<[u8; 4]> = (0u32..20_000).map(u32::to_le_bytes).collect();
+        let builder = || BloomFilterBuilder::new(20_000, 0.001).expect("parameters are correct");
+
+        let parallel = builder().add_element(b"first").add_elements_par(&elements);
+        assert_eq!(parallel.elements_count(), 20_001);
+        let sequential = builder().add_element(b"first").add_elements(&elements);
+        assert_eq!(parallel.build(), sequential.build());
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn parallel_batch_query() {
+        let filter = BloomFilterBuilder::new(1000, 0.001)
+            .expect("parameters are correct")
+            .add_elements((0u32..1000).map(u32::to_le_bytes))
+            .build();
+        let items: Vec<[u8; 4]> = (0u32..5000).map(u32::to_le_bytes).collect();
+
+        let matches = filter.probably_contains_batch_par(&items);
+        assert_eq!(matches, filter.probably_contains_batch(&items));
+        assert!(matches[..1000].all());
+        assert!(filter
+            .probably_contains_batch_par::<[u8; 4]>(&[])
+            .is_empty());
+    }
+
+    #[cfg(feature = "arc-swap")]
+    #[test]
+    fn swappable_filter() {
+        let build = |elements: std::ops::Range<u32>| {
+            BloomFilterBuilder::new(1000, 0.001)
+                .expect("parameters are correct")
+                .add_elements(elements.map(u32::to_le_bytes))
+                .build()
+        };
+        let shared = SwappableBloomFilter::new(build(0..100));
+        let snapshot = shared.load();
+
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                for _ in 0..1000 {
+                    let filter = shared.load();
+                    let old = filter.probably_contains(&0u32.to_le_bytes());
+                    let new = filter.probably_contains(&500u32.to_le_bytes());
+                    assert!(old || new);
+                }
+            });
+            scope.spawn(|| {
+                shared.store(build(500..600));
+            });
+        });
+
+        assert!(shared.probably_contains(&500u32.to_le_bytes()));
+        assert!(snapshot.probably_contains(&0u32.to_le_bytes()));
+        assert_eq!(*snapshot, build(0..100));
+
+        shared.update(|current| {
+            let mut next = current.clone();
+            next.insert(b"added");
+            next
+        });
+        assert!(shared.probably_contains(b"added"));
+        assert!(!snapshot.probably_contains(b"added"));
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn mmap_filter() {
+        let path = std::env::temp_dir().join(format!("bip37-mmap-{}", std::process::id()));
+        let filter = BloomFilterBuilder::new_local(100_000, 0.0001, 9)
+            .expect("parameters are correct")
+            .add_element(b"first")
+            .build_with_n_flags(BloomFlags::UpdateAll);
+        assert!(filter.size_bytes() > MAX_BLOOM_FILTER_SIZE);
+
+        let mut mapped = MmapBloomFilter::create(&path, &filter).expect("file is writable");
+        assert_eq!(mapped.size_bytes(), filter.size_bytes());
+        assert!(mapped.probably_contains(b"first"));
+        assert!(mapped.insert(b"second"));
+        assert!(!mapped.insert(b"second"));
+        mapped.flush().expect("file is writable");
+        drop(mapped);
+
+        let reopened = MmapBloomFilter::open(&path).expect("file is valid");
+        assert!(reopened.probably_contains(b"first"));
+        assert!(reopened.probably_contains(b"second"));
+        let mut expected = filter.clone();
+        expected.insert(b"second");
+        assert_eq!(reopened.to_bloom_filter(), expected);
+        drop(reopened);
+
+        let file = std::fs::OpenOptions::new().write(true).open(&path).unwrap();
+        file.set_len(100).unwrap();
+        let err = MmapBloomFilter::open(&path).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn persistence() {
+        let path = std::env::temp_dir().join(format!("bip37-persist-{}", std::process::id()));
+        let filter = BloomFilterBuilder::new_n_tweak(3, 0.01, 5)
+            .expect("parameters are correct")
+            .add_element(b"first")
+            .add_element(b"second")
+            .build_with_n_flags(BloomFlags::UpdateP2PubkeyOnly);
+
+        filter.save_to(&path).expect("file is writable");
+        let loaded = BloomFilter::load_from(&path).expect("file is valid");
+        assert_eq!(loaded, filter);
+        assert_eq!(loaded.flags(), BloomFlags::UpdateP2PubkeyOnly);
+        assert!(loaded.probably_contains(b"second"));
+        std::fs::remove_file(&path).unwrap();
+
+        let bytes = filter.to_versioned_bytes();
+        assert_eq!(&bytes[..8], b"BIP37FLT");
+        assert_eq!(bytes[8], PERSISTENCE_VERSION);
+
+        let mut corrupted = bytes.clone();
+        corrupted[bytes.len() - 6] ^= 1;
+        assert!(matches!(
+            BloomFilter::from_versioned_bytes(&corrupted),
+            Err(PersistenceError::ChecksumMismatch)
+        ));
+
+        let mut bad_magic = bytes.clone();
+        bad_magic[0] = b'X';
+        assert!(matches!(
+            BloomFilter::from_versioned_bytes(&bad_magic),
+            Err(PersistenceError::BadMagic)
+        ));
+
+        let mut newer = bytes.clone();
+        newer[8] = PERSISTENCE_VERSION + 1;
+        assert!(matches!(
+            BloomFilter::from_versioned_bytes(&newer),
+            Err(PersistenceError::ChecksumMismatch)
+        ));
+        let contents_len = newer.len() - 4;
+        let checksum = hashes::sha256d(&newer[..contents_len]);
+        newer[contents_len..].copy_from_slice(&checksum[..4]);
+        assert!(matches!(
+            BloomFilter::from_versioned_bytes(&newer),
+            Err(PersistenceError::UnsupportedVersion(v)) if v == PERSISTENCE_VERSION + 1
+        ));
+
+        assert!(matches!(
+            BloomFilter::from_versioned_bytes(&bytes[..10]),
+            Err(PersistenceError::Malformed)
+        ));
+        assert!(matches!(
+            BloomFilter::from_versioned_bytes(&bytes[..12]),
+            Err(PersistenceError::ChecksumMismatch)
+        ));
+        assert!(matches!(
+            BloomFilter::load_from(&path),
+            Err(PersistenceError::Io(_))
+        ));
+
+        let local = BloomFilterBuilder::new_local(100_000, 0.0001, 9)
+            .expect("parameters are correct")
+            .add_element(b"first")
+            .build();
+        assert!(local.size_bytes() > MAX_BLOOM_FILTER_SIZE);
+        let loaded = BloomFilter::from_versioned_bytes(&local.to_versioned_bytes())
+            .expect("local filters are stored");
+        assert_eq!(loaded, local);
+    }
 }
@@ -0,0 +1,352 @@
+//! BIP-37 `merkleblock` message construction and verification.
+
+use crate::{hashes, wire, BloomFilter, Transaction};
+
+/// Error type to indicate a `merkleblock` message that cannot be parsed or verified.
+#[derive(Debug, thiserror::Error)]
+pub enum InvalidMerkleBlock {
+    /// Serialized payload is truncated or contains extra bytes
+    #[error("malformed merkleblock payload")]
+    MalformedPayload,
+    /// Partial merkle tree is inconsistent
+    #[error("invalid partial merkle tree: {0}")]
+    InvalidTree(&'static str),
+    /// Computed merkle root doesn't match the one committed in the header
+    #[error("merkle root mismatch")]
+    MerkleRootMismatch,
+}
+
+/// Error type to indicate block transactions a partial merkle tree cannot be built of.
+#[derive(Debug, PartialEq, thiserror::Error)]
+pub enum InvalidBlockTransactions {
+    /// Blocks contain at least the coinbase transaction
+    #[error("no transactions")]
+    NoTransactions,
+    /// Number of transactions exceeds [MAX_BLOCK_TRANSACTIONS]
+    #[error("{0} transactions exceed the block limit")]
+    TooManyTransactions(usize),
+    /// Number of match flags differs from the number of transactions
+    #[error("{0} match flags given for {1} transactions")]
+    MatchFlagsMismatch(usize, usize),
+}
+
+/// Upper bound of the number of transactions in a block as Dash Core checks it: the
+/// DIP-0001 block size limit divided by the smallest serialized transaction size
+pub const MAX_BLOCK_TRANSACTIONS: u32 = 2_000_000 / 60;
+
+/// Block index and txid of a transaction matched by a partial merkle tree
+pub type MatchedTx = (u32, [u8; 32]);
+
+/// Partial merkle tree proving inclusion of matched transactions into a block
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartialMerkleTree {
+    /// Total number of transactions in the block
+    pub n_transactions: u32,
+    /// Node hashes in depth-first order
+    pub hashes: Vec<[u8; 32]>,
+    /// Traversal flag bits in depth-first order
+    pub flags: Vec<bool>,
+}
+
+impl PartialMerkleTree {
+    /// Build a partial merkle tree of the block's transaction ids where `matches[i]`
+    /// tells whether `i`-th transaction should be proven.  [InvalidBlockTransactions]
+    /// returned if there are no txids, more than a block can hold or the number of
+    /// match flags differs.
+    pub fn from_txids(
+        txids: &[[u8; 32]],
+        matches: &[bool],
+    ) -> Result<Self, InvalidBlockTransactions> {
+        let n_transactions = check_transactions_count(txids.len())?;
+        if matches.len() != txids.len() {
+            return Err(InvalidBlockTransactions::MatchFlagsMismatch(
+                matches.len(),
+                txids.len(),
+            ));
+        }
+
+        let mut tree = PartialMerkleTree {
+            n_transactions,
+            hashes: Vec::new(),
+            flags: Vec::new(),
+        };
+
+        let mut height = 0;
+        while tree.width(height) > 1 {
+            height += 1;
+        }
+        tree.traverse_and_build(height, 0, txids, matches);
+
+        Ok(tree)
+    }
+
+    /// Number of nodes at the given height, leaves are at height 0
+    pub(crate) fn width(&self, height: u32) -> u32 {
+        ((u64::from(self.n_transactions) + (1 << height) - 1) >> height) as u32
+    }
+
+    fn calc_hash(&self, height: u32, pos: u32, txids: &[[u8; 32]]) -> [u8; 32] {
+        if height == 0 {
+            return txids[pos as usize];
+        }
+
+        let left = self.calc_hash(height - 1, pos * 2, txids);
+        let right = if pos * 2 + 1 < self.width(height - 1) {
+            self.calc_hash(height - 1, pos * 2 + 1, txids)
+        } else {
+            left
+        };
+        hash_nodes(&left, &right)
+    }
+
+    fn traverse_and_build(&mut self, height: u32, pos: u32, txids: &[[u8; 32]], matches: &[bool]) {
+        let start = (pos << height) as usize;
+        let end = (((pos + 1) << height) as usize).min(matches.len());
+        let parent_of_match = matches[start..end].iter().any(|m| *m);
+
+        self.flags.push(parent_of_match);
+        if height == 0 || !parent_of_match {
+            let hash = self.calc_hash(height, pos, txids);
+            self.hashes.push(hash);
+        } else {
+            self.traverse_and_build(height - 1, pos * 2, txids, matches);
+            if pos * 2 + 1 < self.width(height - 1) {
+                self.traverse_and_build(height - 1, pos * 2 + 1, txids, matches);
+            }
+        }
+    }
+
+    /// Compute the merkle root and extract matched transactions as pairs of block index
+    /// and txid, validating the tree structure along the way
+    pub fn extract_matches(&self) -> Result<([u8; 32], Vec<MatchedTx>), InvalidMerkleBlock> {
+        if self.n_transactions == 0 {
+            return Err(InvalidMerkleBlock::InvalidTree("no transactions"));
+        }
+        if self.n_transactions > MAX_BLOCK_TRANSACTIONS {
+            return Err(InvalidMerkleBlock::InvalidTree(
+                "more transactions than a block can hold",
+            ));
+        }
+        if self.hashes.len() > self.n_transactions as usize {
+            return Err(InvalidMerkleBlock::InvalidTree(
+                "more hashes than transactions",
+            ));
+        }
+        if self.flags.len() < self.hashes.len() {
+            return Err(InvalidMerkleBlock::InvalidTree("fewer flags than hashes"));
+        }
+
+        let mut height = 0;
+        while self.width(height) > 1 {
+            height += 1;
+        }
+
+        let mut extractor = Extractor {
+            tree: self,
+            bits_used: 0,
+            hashes_used: 0,
+            matches: Vec::new(),
+        };
+        let root = extractor.traverse_and_extract(height, 0)?;
+
+        if extractor.bits_used.div_ceil(8) != self.flags.len().div_ceil(8) {
+            return Err(InvalidMerkleBlock::InvalidTree("not all flags consumed"));
+        }
+        if extractor.hashes_used != self.hashes.len() {
+            return Err(InvalidMerkleBlock::InvalidTree("not all hashes consumed"));
+        }
+
+        Ok((root, extractor.matches))
+    }
+
+    fn read(reader: &mut wire::Reader) -> Option<Self> {
+        let n_transactions = reader.read_u32_le()?;
+
+        let n_hashes = reader.read_compact_size()?;
+        let mut hashes = Vec::new();
+        for _ in 0..n_hashes {
+            hashes.push(reader.read_bytes(32)?.try_into().expect("length checked"));
+        }
+
+        let n_flag_bytes = reader.read_compact_size()?;
+        let flag_bytes = reader.read_bytes(n_flag_bytes.try_into().ok()?)?;
+        let flags = flag_bytes
+            .iter()
+            .flat_map(|byte| (0..8).map(move |i| byte >> i & 1 == 1))
+            .collect();
+
+        Some(PartialMerkleTree {
+            n_transactions,
+            hashes,
+            flags,
+        })
+    }
+
+    /// Append the tree in wire format to the buffer
+    pub(crate) fn write(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&self.n_transactions.to_le_bytes());
+
+        wire::write_compact_size(buf, self.hashes.len() as u64);
+        for hash in &self.hashes {
+            buf.extend_from_slice(hash);
+        }
+
+        let mut flag_bytes = vec![0u8; self.flags.len().div_ceil(8)];
+        for (i, flag) in self.flags.iter().enumerate() {
+            flag_bytes[i / 8] |= (*flag as u8) << (i % 8);
+        }
+        wire::write_compact_size(buf, flag_bytes.len() as u64);
+        buf.extend_from_slice(&flag_bytes);
+    }
+}
+
+struct Extractor<'a> {
+    tree: &'a PartialMerkleTree,
+    bits_used: usize,
+    hashes_used: usize,
+    matches: Vec<MatchedTx>,
+}
+
+impl Extractor<'_> {
+    fn traverse_and_extract(
+        &mut self,
+        height: u32,
+        pos: u32,
+    ) -> Result<[u8; 32], InvalidMerkleBlock> {
+        let parent_of_match = *self
+            .tree
+            .flags
+            .get(self.bits_used)
+            .ok_or(InvalidMerkleBlock::InvalidTree("ran out of flags"))?;
+        self.bits_used += 1;
+
+        if height == 0 || !parent_of_match {
+            let hash = *self
+                .tree
+                .hashes
+                .get(self.hashes_used)
+                .ok_or(InvalidMerkleBlock::InvalidTree("ran out of hashes"))?;
+            self.hashes_used += 1;
+            if height == 0 && parent_of_match {
+                self.matches.push((pos, hash));
+            }
+            return Ok(hash);
+        }
+
+        let left = self.traverse_and_extract(height - 1, pos * 2)?;
+        let right = if pos * 2 + 1 < self.tree.width(height - 1) {
+            let right = self.traverse_and_extract(height - 1, pos * 2 + 1)?;
+            // CVE-2012-2459: identical siblings allow forging trees with duplicates
+            if right == left {
+                return Err(InvalidMerkleBlock::InvalidTree("duplicate sibling hashes"));
+            }
+            right
+        } else {
+            left
+        };
+
+        Ok(hash_nodes(&left, &right))
+    }
+}
+
+fn check_transactions_count(n_transactions: usize) -> Result<u32, InvalidBlockTransactions> {
+    match u32::try_from(n_transactions) {
+        Ok(0) => Err(InvalidBlockTransactions::NoTransactions),
+        Ok(n) if n <= MAX_BLOCK_TRANSACTIONS => Ok(n),
+        _ => Err(InvalidBlockTransactions::TooManyTransactions(
+            n_transactions,
+        )),
+    }
+}
+
+pub(crate) fn hash_nodes(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut buf = [0; 64];
+    buf[..32].copy_from_slice(left);
+    buf[32..].copy_from_slice(right);
+    hashes::sha256d(&buf)
+}
+
+/// BIP-37 `merkleblock` message: block header with a partial merkle tree of
+/// transactions matched by a peer's filter
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MerkleBlock {
+    /// Serialized 80 bytes block header
+    pub header: [u8; 80],
+    /// Proof of matched transactions inclusion
+    pub txn: PartialMerkleTree,
+}
+
+impl MerkleBlock {
+    /// Match block transactions against the filter, updating it according to its
+    /// [BloomFlags](crate::BloomFlags) like Dash Core does.  Returns the message
+    /// together with indexes of matched transactions, which should be relayed to the peer
+    /// after the message.  [InvalidBlockTransactions] returned if there are no
+    /// transactions or more than a block can hold, the filter is left unchanged then.
+    pub fn from_block(
+        header: [u8; 80],
+        transactions: &[Transaction],
+        filter: &mut BloomFilter,
+    ) -> Result<(Self, Vec<usize>), InvalidBlockTransactions> {
+        check_transactions_count(transactions.len())?;
+
+        let matches: Vec<bool> = transactions
+            .iter()
+            .map(|tx| filter.matches_transaction_and_update(tx))
+            .collect();
+        let txids: Vec<[u8; 32]> = transactions.iter().map(|tx| tx.txid).collect();
+
+        let matched = matches
+            .iter()
+            .enumerate()
+            .filter_map(|(i, m)| m.then_some(i))
+            .collect();
+        let merkle_block = MerkleBlock {
+            header,
+            txn: PartialMerkleTree::from_txids(&txids, &matches)?,
+        };
+
+        Ok((merkle_block, matched))
+    }
+
+    /// Deserialize from a payload of `merkleblock` P2P message
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, InvalidMerkleBlock> {
+        let mut reader = wire::Reader::new(bytes);
+
+        let header = reader
+            .read_bytes(80)
+            .ok_or(InvalidMerkleBlock::MalformedPayload)?
+            .try_into()
+            .expect("length checked");
+        let txn =
+            PartialMerkleTree::read(&mut reader).ok_or(InvalidMerkleBlock::MalformedPayload)?;
+
+        if !reader.is_empty() {
+            return Err(InvalidMerkleBlock::MalformedPayload);
+        }
+
+        Ok(MerkleBlock { header, txn })
+    }
+
+    /// Merkle root committed in the block header
+    pub fn merkle_root(&self) -> [u8; 32] {
+        self.header[36..68].try_into().expect("header is 80 bytes")
+    }
+
+    /// Verify the partial merkle tree against the header's merkle root and return
+    /// matched transactions as pairs of block index and txid
+    pub fn verify(&self) -> Result<Vec<MatchedTx>, InvalidMerkleBlock> {
+        let (root, matches) = self.txn.extract_matches()?;
+        if root != self.merkle_root() {
+            return Err(InvalidMerkleBlock::MerkleRootMismatch);
+        }
+        Ok(matches)
+    }
+
+    /// Serialize as a payload of `merkleblock` P2P message
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(80 + 4 + 9 + self.txn.hashes.len() * 32 + 9);
+        buf.extend_from_slice(&self.header);
+        self.txn.write(&mut buf);
+        buf
+    }
+}
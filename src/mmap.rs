@@ -0,0 +1,149 @@
+//! Bloom filter with bits kept in a memory-mapped file.
+
+use std::{
+    fs::OpenOptions,
+    io::{self, ErrorKind},
+    path::Path,
+};
+
+use memmap2::MmapMut;
+
+use crate::{filter, hasher::Hasher, BloomFilter, BloomFlags, MAX_HASH_FUNCS};
+
+const MAGIC: &[u8; 8] = b"BIP37MAP";
+
+/// Magic, `nHashFuncs`, `nTweak`, `nFlags` padded to 4 bytes and filter size in bytes
+const HEADER_LEN: usize = 8 + 4 + 4 + 4 + 8;
+
+/// Bloom filter whose bits live in a memory-mapped file, so filters too large to be sent
+/// to peers, see [BloomFilterBuilder::new_local](crate::BloomFilterBuilder::new_local),
+/// survive restarts and are paged in on demand instead of being read into memory.
+///
+/// The file holds a header with the filter parameters followed by the filter bytes in
+/// BIP-37 layout.  Changes reach the file when the operating system writes the pages
+/// back, or on [Self::flush].  The file must not be modified or truncated by other
+/// processes while it is mapped.
+#[derive(Debug)]
+pub struct MmapBloomFilter {
+    map: MmapMut,
+    n_tweak: u32,
+    n_flags: BloomFlags,
+    hasher: Hasher,
+}
+
+impl MmapBloomFilter {
+    /// Create the file at `path` holding a copy of the filter, overwriting an existing
+    /// one, and map it
+    pub fn create(path: impl AsRef<Path>, filter: &BloomFilter) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+
+        let size = filter.size_bytes();
+        file.set_len((HEADER_LEN + size) as u64)?;
+        // SAFETY: the file must not be changed by others while mapped, as documented
+        let mut map = unsafe { MmapMut::map_mut(&file)? };
+
+        map[..8].copy_from_slice(MAGIC);
+        map[8..12].copy_from_slice(&filter.n_hash_funcs().to_le_bytes());
+        map[12..16].copy_from_slice(&filter.n_tweak.to_le_bytes());
+        map[16..20].copy_from_slice(&u32::from(u8::from(filter.n_flags)).to_le_bytes());
+        map[20..28].copy_from_slice(&(size as u64).to_le_bytes());
+        map[HEADER_LEN..].copy_from_slice(&filter::bits_to_bytes(&filter.filter_bits));
+
+        Ok(MmapBloomFilter {
+            map,
+            n_tweak: filter.n_tweak,
+            n_flags: filter.n_flags,
+            hasher: filter.hasher.clone(),
+        })
+    }
+
+    /// Map the file at `path` created by [Self::create].  [ErrorKind::InvalidData]
+    /// returned if it is not such a file or is truncated.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        // SAFETY: the file must not be changed by others while mapped, as documented
+        let map = unsafe { MmapMut::map_mut(&file)? };
+
+        let invalid = |msg: &str| io::Error::new(ErrorKind::InvalidData, msg.to_owned());
+
+        if map.len() < HEADER_LEN || &map[..8] != MAGIC {
+            return Err(invalid("not a memory-mapped Bloom filter file"));
+        }
+        let read_u32 = |offset: usize| {
+            u32::from_le_bytes(map[offset..offset + 4].try_into().expect("4 bytes"))
+        };
+        let n_hash_funcs = read_u32(8);
+        let n_tweak = read_u32(12);
+        let n_flags = u8::try_from(read_u32(16))
+            .ok()
+            .and_then(|n_flags| BloomFlags::try_from(n_flags).ok())
+            .ok_or_else(|| invalid("unknown nFlags"))?;
+        let size = u64::from_le_bytes(map[20..28].try_into().expect("8 bytes"));
+
+        if size == 0 || n_hash_funcs > MAX_HASH_FUNCS {
+            return Err(invalid("invalid filter parameters"));
+        }
+        if (map.len() - HEADER_LEN) as u64 != size {
+            return Err(invalid("filter size doesn't match the file size"));
+        }
+
+        Ok(MmapBloomFilter {
+            hasher: Hasher::new(n_hash_funcs, n_tweak, size as usize * 8),
+            map,
+            n_tweak,
+            n_flags,
+        })
+    }
+
+    fn bytes(&self) -> &[u8] {
+        &self.map[HEADER_LEN..]
+    }
+
+    /// Add element to the filter, returns `false` if it was already covered by the
+    /// filter
+    pub fn insert(&mut self, element: &[u8]) -> bool {
+        let mut changed = false;
+        for i in self.hasher.hash_indexes(element) {
+            let byte = &mut self.map[HEADER_LEN + i / 8];
+            let mask = 1 << (i % 8);
+            changed |= *byte & mask == 0;
+            *byte |= mask;
+        }
+        changed
+    }
+
+    /// Check if the filter possibly contains the element
+    pub fn probably_contains(&self, element: &[u8]) -> bool {
+        let bytes = self.bytes();
+        self.hasher
+            .hash_indexes(element)
+            .all(|i| bytes[i / 8] & (1 << (i % 8)) != 0)
+    }
+
+    /// Write changes back to the file
+    pub fn flush(&self) -> io::Result<()> {
+        self.map.flush()
+    }
+
+    /// Get filter size in bytes
+    pub fn size_bytes(&self) -> usize {
+        self.bytes().len()
+    }
+
+    /// Copy the filter into memory
+    pub fn to_bloom_filter(&self) -> BloomFilter {
+        BloomFilter {
+            filter_bits: filter::bits_from_bytes(self.bytes()),
+            n_tweak: self.n_tweak,
+            n_flags: self.n_flags,
+            hasher: self.hasher.clone(),
+            pending_filteradd: None,
+            dirty: true,
+        }
+    }
+}
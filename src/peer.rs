@@ -0,0 +1,102 @@
+//! Node side handling of BIP-37 messages received from a peer.
+
+use crate::{wire, BloomFilter, InvalidFilterData, Transaction, MAX_FILTERADD_SIZE};
+
+/// Error type to indicate that a peer sent an invalid BIP-37 message, Dash Core treats
+/// every case as misbehavior.
+#[derive(Debug, thiserror::Error)]
+pub enum InvalidFilterMessage {
+    /// `filterload` payload is malformed or violates BIP-37 limits
+    #[error("invalid filterload: {0}")]
+    InvalidFilter(#[from] InvalidFilterData),
+    /// `filteradd` payload is truncated or contains extra bytes
+    #[error("malformed filteradd payload")]
+    MalformedPayload,
+    /// `filteradd` element exceeds [MAX_FILTERADD_SIZE]
+    #[error("filteradd element of {0} bytes exceeds the maximum of {MAX_FILTERADD_SIZE} bytes")]
+    ElementTooLarge(usize),
+    /// `filteradd` received while no filter is loaded
+    #[error("filteradd received without a filter loaded")]
+    NoFilterLoaded,
+}
+
+/// Filter a peer has loaded, updated by `filterload`, `filteradd` and `filterclear`
+/// messages the way Dash Core does it.  Until a filter is loaded, or once it is cleared,
+/// all transactions are relayed to the peer.
+#[derive(Debug, Clone, Default)]
+pub struct PeerFilterState {
+    filter: Option<BloomFilter>,
+}
+
+impl PeerFilterState {
+    /// Create state of a peer that has no filter loaded
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Handle `filterload` payload, replacing the loaded filter.  [InvalidFilterMessage]
+    /// returned and the state left unchanged if the filter is malformed or violates
+    /// BIP-37 limits.  Like Dash Core, unknown `nFlags` bits are ignored and an empty
+    /// filter is accepted, relaying everything.
+    pub fn filterload(&mut self, payload: &[u8]) -> Result<(), InvalidFilterMessage> {
+        self.filter = Some(BloomFilter::from_filterload_bytes_lenient(payload)?);
+        Ok(())
+    }
+
+    /// Handle `filteradd` payload, adding the element to the loaded filter.
+    /// [InvalidFilterMessage] returned if the payload is malformed, the element exceeds
+    /// [MAX_FILTERADD_SIZE] or there is no filter loaded.
+    pub fn filteradd(&mut self, payload: &[u8]) -> Result<(), InvalidFilterMessage> {
+        let mut reader = wire::Reader::new(payload);
+        let len = reader
+            .read_compact_size()
+            .ok_or(InvalidFilterMessage::MalformedPayload)?;
+        if len > MAX_FILTERADD_SIZE as u64 {
+            return Err(InvalidFilterMessage::ElementTooLarge(
+                len.try_into().unwrap_or(usize::MAX),
+            ));
+        }
+        let element = reader
+            .read_bytes(len as usize)
+            .ok_or(InvalidFilterMessage::MalformedPayload)?;
+        if !reader.is_empty() {
+            return Err(InvalidFilterMessage::MalformedPayload);
+        }
+
+        let filter = self
+            .filter
+            .as_mut()
+            .ok_or(InvalidFilterMessage::NoFilterLoaded)?;
+        filter.insert(element);
+        Ok(())
+    }
+
+    /// Handle `filterclear`, removing the loaded filter
+    pub fn filterclear(&mut self) {
+        self.filter = None;
+    }
+
+    /// Get the loaded filter
+    pub fn filter(&self) -> Option<&BloomFilter> {
+        self.filter.as_ref()
+    }
+
+    /// Check if the transaction is to be relayed to the peer: either no filter is loaded
+    /// or the filter matches it.  The filter is not updated, see
+    /// [BloomFilter::matches_transaction].
+    pub fn relays_transaction(&self, tx: &Transaction) -> bool {
+        self.filter
+            .as_ref()
+            .is_none_or(|filter| filter.matches_transaction(tx))
+    }
+
+    /// Decide whether the transaction is to be relayed to the peer like at
+    /// [Self::relays_transaction], updating the filter with matched outputs according to
+    /// its flags the way the peer does, see [BloomFilter::matches_transaction_and_update].
+    /// This is what Dash Core does before relaying a transaction to a peer.
+    pub fn should_relay_transaction(&mut self, tx: &Transaction) -> bool {
+        self.filter
+            .as_mut()
+            .is_none_or(|filter| filter.matches_transaction_and_update(tx))
+    }
+}
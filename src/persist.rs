@@ -0,0 +1,130 @@
+//! Versioned file format for storing filters with integrity protection.
+
+use std::{
+    fs,
+    io::{self, Read, Write},
+    path::Path,
+};
+
+use crate::{hashes, wire, BloomFilter, BloomFilterData, InvalidFilterData};
+
+const MAGIC: &[u8; 8] = b"BIP37FLT";
+
+/// Current version of the file format
+pub const PERSISTENCE_VERSION: u8 = 1;
+
+/// Length of the checksum, first bytes of `SHA256(SHA256(contents))` like Dash P2P
+/// message checksums
+const CHECKSUM_LEN: usize = 4;
+
+/// Error type to indicate that a filter cannot be stored or loaded.
+#[derive(Debug, thiserror::Error)]
+pub enum PersistenceError {
+    /// Reading or writing failed
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    /// Data doesn't start with the magic bytes
+    #[error("not a stored Bloom filter")]
+    BadMagic,
+    /// Data was written by a newer version of the format
+    #[error("unsupported format version {0}")]
+    UnsupportedVersion(u8),
+    /// Data is truncated or contains extra bytes
+    #[error("stored filter is truncated or malformed")]
+    Malformed,
+    /// Checksum doesn't match the contents, the data is corrupted
+    #[error("checksum mismatch, stored filter is corrupted")]
+    ChecksumMismatch,
+    /// Stored parameters are invalid
+    #[error(transparent)]
+    InvalidFilter(#[from] InvalidFilterData),
+}
+
+impl BloomFilter {
+    /// Serialize the filter in a versioned format: magic `BIP37FLT`, version byte,
+    /// `nHashFuncs`, `nTweak`, `nFlags`, the filter bytes prefixed with their length and
+    /// a checksum of everything before it.  Filters exceeding BIP-37 size limit are
+    /// stored as well.
+    pub fn to_versioned_bytes(&self) -> Vec<u8> {
+        let v_data = crate::filter::bits_to_bytes(&self.filter_bits);
+        let mut buf = Vec::with_capacity(v_data.len() + 32);
+
+        buf.extend_from_slice(MAGIC);
+        buf.push(PERSISTENCE_VERSION);
+        buf.extend_from_slice(&self.n_hash_funcs().to_le_bytes());
+        buf.extend_from_slice(&self.n_tweak.to_le_bytes());
+        buf.push(self.n_flags.into());
+        wire::write_compact_size(&mut buf, v_data.len() as u64);
+        buf.extend_from_slice(&v_data);
+
+        let checksum = hashes::sha256d(&buf);
+        buf.extend_from_slice(&checksum[..CHECKSUM_LEN]);
+        buf
+    }
+
+    /// Deserialize the filter stored with [Self::to_versioned_bytes].
+    /// [PersistenceError] returned if the data is corrupted, of an unknown version or
+    /// describes an invalid filter.
+    pub fn from_versioned_bytes(bytes: &[u8]) -> Result<Self, PersistenceError> {
+        if bytes.len() < MAGIC.len() || &bytes[..MAGIC.len()] != MAGIC {
+            return Err(PersistenceError::BadMagic);
+        }
+        if bytes.len() < MAGIC.len() + CHECKSUM_LEN {
+            return Err(PersistenceError::Malformed);
+        }
+        let (contents, checksum) = bytes.split_at(bytes.len() - CHECKSUM_LEN);
+
+        if hashes::sha256d(contents)[..CHECKSUM_LEN] != *checksum {
+            return Err(PersistenceError::ChecksumMismatch);
+        }
+
+        let mut reader = wire::Reader::new(&contents[MAGIC.len()..]);
+        let version = reader.read_u8().ok_or(PersistenceError::Malformed)?;
+        if version != PERSISTENCE_VERSION {
+            return Err(PersistenceError::UnsupportedVersion(version));
+        }
+
+        let data = Self::read_versioned(&mut reader).ok_or(PersistenceError::Malformed)?;
+        if !reader.is_empty() {
+            return Err(PersistenceError::Malformed);
+        }
+        Ok(Self::from_data_local(data)?)
+    }
+
+    fn read_versioned(reader: &mut wire::Reader) -> Option<BloomFilterData> {
+        let n_hash_funcs = reader.read_u32_le()?;
+        let n_tweak = reader.read_u32_le()?;
+        let n_flags = reader.read_u8()?.into();
+        let v_data_len = usize::try_from(reader.read_compact_size()?).ok()?;
+        let v_data = reader.read_bytes(v_data_len)?.to_vec();
+
+        Some(BloomFilterData {
+            v_data,
+            n_hash_funcs,
+            n_tweak,
+            n_flags,
+        })
+    }
+
+    /// Store the filter at `path` in the format of [Self::to_versioned_bytes].  It is
+    /// written to a temporary file next to `path` first and renamed over it, so an
+    /// interrupted save leaves the previous file intact.
+    pub fn save_to(&self, path: impl AsRef<Path>) -> Result<(), PersistenceError> {
+        let path = path.as_ref();
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+
+        let mut file = fs::File::create(&tmp_path)?;
+        file.write_all(&self.to_versioned_bytes())?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, path)?;
+        Ok(())
+    }
+
+    /// Load the filter stored with [Self::save_to], see [Self::from_versioned_bytes]
+    pub fn load_from(path: impl AsRef<Path>) -> Result<Self, PersistenceError> {
+        let mut bytes = Vec::new();
+        fs::File::open(path)?.read_to_end(&mut bytes)?;
+        Self::from_versioned_bytes(&bytes)
+    }
+}
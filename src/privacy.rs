@@ -0,0 +1,324 @@
+//! Mitigations for BIP-37 privacy weaknesses.
+//!
+//! A peer holding a wallet's filter learns which addresses likely belong to it.  The
+//! helpers here limit what a single peer can learn.
+
+use std::{
+    num::NonZeroUsize,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    hashes, merkleblock::MatchedTx, BadFilterParameters, BloomFilter, BloomFilterBuilder,
+    BloomFlags,
+};
+
+/// Split elements across `n_filters` filters, one per peer, so no single peer learns the
+/// full set.  Every element is assigned by its hash, so it goes to the same filter no
+/// matter what other elements there are and rebuilt filters don't leak more elements.
+/// Each filter is sized for its own share of elements.
+///
+/// Matches returned by the peers can be merged with [combine_matches].
+pub fn split_filters<I>(
+    elements: I,
+    n_filters: NonZeroUsize,
+    false_positives_rate: f64,
+    n_tweak: u32,
+    n_flags: BloomFlags,
+) -> Result<Vec<BloomFilter>, BadFilterParameters>
+where
+    I: IntoIterator,
+    I::Item: AsRef<[u8]>,
+{
+    let mut partitions = vec![Vec::new(); n_filters.get()];
+    for element in elements {
+        let element = element.as_ref();
+        partitions[partition_index(element, n_filters)].push(element.to_vec());
+    }
+
+    partitions
+        .into_iter()
+        .map(|partition| {
+            let n_elements = u32::try_from(partition.len().max(1)).unwrap_or(u32::MAX);
+            Ok(
+                BloomFilterBuilder::new_n_tweak(n_elements, false_positives_rate, n_tweak)?
+                    .add_elements(partition)
+                    .build_with_n_flags(n_flags),
+            )
+        })
+        .collect()
+}
+
+/// Build a filter for each of `n_tweaks` over the same elements, so every peer gets a
+/// differently randomized filter and peers can't tell they are watching the same wallet
+/// by comparing filters.  Bloom filters can't be rehashed, so the elements themselves
+/// are needed.
+pub fn per_peer_filters<T: AsRef<[u8]>>(
+    elements: &[T],
+    n_tweaks: &[u32],
+    false_positives_rate: f64,
+    n_flags: BloomFlags,
+) -> Result<Vec<BloomFilter>, BadFilterParameters> {
+    let n_elements = u32::try_from(elements.len().max(1)).unwrap_or(u32::MAX);
+    n_tweaks
+        .iter()
+        .map(|&n_tweak| {
+            Ok(
+                BloomFilterBuilder::new_n_tweak(n_elements, false_positives_rate, n_tweak)?
+                    .add_elements(elements)
+                    .build_with_n_flags(n_flags),
+            )
+        })
+        .collect()
+}
+
+fn partition_index(element: &[u8], n_filters: NonZeroUsize) -> usize {
+    let hash = hashes::sha256d(element);
+    let n = u64::from_le_bytes(hash[..8].try_into().expect("hash is 32 bytes"));
+    (n % n_filters.get() as u64) as usize
+}
+
+/// Merge transactions of a block matched by several peers' filters into a single list
+/// ordered by position in the block, with duplicates removed
+pub fn combine_matches<I>(per_filter: I) -> Vec<MatchedTx>
+where
+    I: IntoIterator<Item = Vec<MatchedTx>>,
+{
+    let mut matches: Vec<MatchedTx> = per_filter.into_iter().flatten().collect();
+    matches.sort_unstable();
+    matches.dedup();
+    matches
+}
+
+/// Derive `nTweak` for the epoch from a wallet secret as `HMAC-SHA256(seed, epoch)`, so
+/// every session gets its own tweak, yet the filter can be regenerated after a restart
+/// without storing the tweak.  `epoch` is encoded as little-endian and the first 4 bytes
+/// of the MAC are taken as little-endian `nTweak`.
+pub fn derive_n_tweak(seed: &[u8], epoch: u64) -> u32 {
+    let mac = hashes::hmac_sha256(seed, &epoch.to_le_bytes());
+    u32::from_le_bytes(mac[..4].try_into().expect("MAC is 32 bytes"))
+}
+
+/// Estimate how many unrelated addresses match the filter per each of its `n_elements`
+/// real elements, given that `address_space` addresses are in use overall.  The larger
+/// the number, the less a peer learns from a match; below one most matches belong to
+/// the wallet.
+pub fn expected_anonymity_set(filter: &BloomFilter, n_elements: u32, address_space: u64) -> f64 {
+    let unrelated = address_space.saturating_sub(n_elements.into()) as f64;
+    filter.effective_fp_rate(n_elements) * unrelated / n_elements.max(1) as f64
+}
+
+/// When a [RotatingFilter] is to be regenerated
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RotationPolicy {
+    /// After the time has passed since the previous rotation
+    Elapsed(Duration),
+    /// After the number of blocks was seen since the previous rotation
+    Blocks(u32),
+    /// After the number of queries was served since the previous rotation
+    Queries(u64),
+}
+
+/// Filter which is regenerated with a new `nTweak`, and new decoys if configured, on the
+/// schedule of its [RotationPolicy], so peers can't fingerprint a wallet by a long-lived
+/// filter.  Every rotation produces a `filterload` payload to be sent to peers.
+#[derive(Debug, Clone)]
+pub struct RotatingFilter {
+    /// Sorted without duplicates, so re-inserted elements don't inflate rebuilt filters
+    elements: Vec<Vec<u8>>,
+    decoys: Vec<[u8; 32]>,
+    false_positives_rate: f64,
+    n_flags: BloomFlags,
+    policy: RotationPolicy,
+    filter: BloomFilter,
+    rotated_at: Instant,
+    blocks_seen: u32,
+    queries_served: u64,
+    #[cfg(feature = "rand")]
+    decoy_fp_rate: Option<f64>,
+}
+
+impl RotatingFilter {
+    /// Create new rotating filter over the elements.  [BadFilterParameters] returned if
+    /// the false positives rate is not within `(0, 1)` or the filter would exceed BIP-37
+    /// limits.
+    pub fn new<I>(
+        elements: I,
+        false_positives_rate: f64,
+        n_tweak: u32,
+        n_flags: BloomFlags,
+        policy: RotationPolicy,
+    ) -> Result<Self, BadFilterParameters>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<[u8]>,
+    {
+        let mut elements: Vec<Vec<u8>> = elements
+            .into_iter()
+            .map(|element| element.as_ref().to_vec())
+            .collect();
+        elements.sort_unstable();
+        elements.dedup();
+        let filter =
+            Self::builder(&elements, false_positives_rate, n_tweak)?.build_with_n_flags(n_flags);
+
+        Ok(RotatingFilter {
+            elements,
+            decoys: Vec::new(),
+            false_positives_rate,
+            n_flags,
+            policy,
+            filter,
+            rotated_at: Instant::now(),
+            blocks_seen: 0,
+            queries_served: 0,
+            #[cfg(feature = "rand")]
+            decoy_fp_rate: None,
+        })
+    }
+
+    /// Add decoys until the false positives rate reaches `target_fp_rate`, see
+    /// [BloomFilterBuilder::decoys_for_fp_rate].  The current filter is rebuilt with
+    /// decoys drawn from `rng`.  [Self::rotate_with_rng] draws fresh decoys, while
+    /// [Self::rotate] rehashes the current ones with the new `nTweak`, so every filter
+    /// carries them.  [BadFilterParameters] returned if the rate is not within `(0, 1)`.
+    #[cfg(feature = "rand")]
+    pub fn with_decoys(
+        mut self,
+        target_fp_rate: f64,
+        rng: &mut impl rand_core::RngCore,
+    ) -> Result<Self, BadFilterParameters> {
+        BloomFilterBuilder::check_fp_rate(target_fp_rate)?;
+        self.decoy_fp_rate = Some(target_fp_rate);
+        self.regenerate(self.filter.n_tweak(), rng)?;
+        Ok(self)
+    }
+
+    fn builder(
+        elements: &[Vec<u8>],
+        false_positives_rate: f64,
+        n_tweak: u32,
+    ) -> Result<BloomFilterBuilder, BadFilterParameters> {
+        let n_elements = u32::try_from(elements.len().max(1)).unwrap_or(u32::MAX);
+        Ok(
+            BloomFilterBuilder::new_n_tweak(n_elements, false_positives_rate, n_tweak)?
+                .add_elements(elements),
+        )
+    }
+
+    /// Add element to the filter, it is kept for future rotations.  Returns `false` if the
+    /// element was already covered by the filter.
+    pub fn insert(&mut self, element: &[u8]) -> bool {
+        let search = self
+            .elements
+            .binary_search_by(|kept| kept.as_slice().cmp(element));
+        if let Err(pos) = search {
+            self.elements.insert(pos, element.to_vec());
+        }
+        self.filter.insert(element)
+    }
+
+    /// Count a block towards [RotationPolicy::Blocks]
+    pub fn record_block(&mut self) {
+        self.blocks_seen = self.blocks_seen.saturating_add(1);
+    }
+
+    /// Count a query towards [RotationPolicy::Queries]
+    pub fn record_query(&mut self) {
+        self.queries_served = self.queries_served.saturating_add(1);
+    }
+
+    /// Check if the filter is to be rotated according to its policy
+    pub fn rotation_due(&self) -> bool {
+        match self.policy {
+            RotationPolicy::Elapsed(duration) => self.rotated_at.elapsed() >= duration,
+            RotationPolicy::Blocks(blocks) => self.blocks_seen >= blocks,
+            RotationPolicy::Queries(queries) => self.queries_served >= queries,
+        }
+    }
+
+    /// Rebuild the filter with `n_tweak`, keeping the current decoys, and restart the
+    /// schedule.  Returns the `filterload` payload to be sent to peers.
+    pub fn rotate(&mut self, n_tweak: u32) -> Result<Vec<u8>, BadFilterParameters> {
+        let builder = Self::builder(&self.elements, self.false_positives_rate, n_tweak)?
+            .add_elements(&self.decoys);
+        Ok(self.replace(builder))
+    }
+
+    /// Rotate the filter like at [Self::rotate] if it is due, otherwise `None` is returned
+    pub fn rotate_if_due(&mut self, n_tweak: u32) -> Result<Option<Vec<u8>>, BadFilterParameters> {
+        if !self.rotation_due() {
+            return Ok(None);
+        }
+        self.rotate(n_tweak).map(Some)
+    }
+
+    /// Rebuild the filter like at [Self::rotate] with a random `nTweak` and fresh decoys
+    /// if configured with [Self::with_decoys]
+    #[cfg(feature = "rand")]
+    pub fn rotate_with_rng(
+        &mut self,
+        rng: &mut impl rand_core::RngCore,
+    ) -> Result<Vec<u8>, BadFilterParameters> {
+        let n_tweak = rng.next_u32();
+        self.regenerate(n_tweak, rng)
+    }
+
+    #[cfg(feature = "rand")]
+    fn regenerate(
+        &mut self,
+        n_tweak: u32,
+        rng: &mut impl rand_core::RngCore,
+    ) -> Result<Vec<u8>, BadFilterParameters> {
+        let mut builder = Self::builder(&self.elements, self.false_positives_rate, n_tweak)?;
+        if let Some(decoy_fp_rate) = self.decoy_fp_rate {
+            let count = builder.decoys_for_fp_rate(decoy_fp_rate)?;
+            self.decoys = (0..count)
+                .map(|_| {
+                    let mut decoy = [0; 32];
+                    rng.fill_bytes(&mut decoy);
+                    decoy
+                })
+                .collect();
+            builder = builder.add_elements(&self.decoys);
+        }
+        Ok(self.replace(builder))
+    }
+
+    fn replace(&mut self, builder: BloomFilterBuilder) -> Vec<u8> {
+        self.filter = builder.build_with_n_flags(self.n_flags);
+        self.rotated_at = Instant::now();
+        self.blocks_seen = 0;
+        self.queries_served = 0;
+        self.filter.to_filterload_bytes()
+    }
+
+    /// Get the current filter
+    pub fn filter(&self) -> &BloomFilter {
+        &self.filter
+    }
+}
+
+/// Wipes the retained elements, decoys and the filter.  The filter is replaced with an
+/// empty one with zero `nTweak`, so new elements can still be inserted.
+#[cfg(feature = "zeroize")]
+impl zeroize::Zeroize for RotatingFilter {
+    fn zeroize(&mut self) {
+        self.elements.zeroize();
+        self.decoys.zeroize();
+        self.filter.zeroize();
+        self.filter = Self::builder(&self.elements, self.false_positives_rate, 0)
+            .expect("false positives rate was checked on creation")
+            .build_with_n_flags(self.n_flags);
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl Drop for RotatingFilter {
+    fn drop(&mut self) {
+        zeroize::Zeroize::zeroize(self);
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl zeroize::ZeroizeOnDrop for RotatingFilter {}
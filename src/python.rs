@@ -0,0 +1,116 @@
+//! Python bindings built with `pyo3`.
+//!
+//! The module is named `dashcore_bloom_filter`.  Wheels are built with maturin, enabling
+//! `pyo3/extension-module`.
+
+// Code generated by `#[pymethods]` for fallible methods converts `PyErr` into itself
+#![allow(clippy::useless_conversion)]
+
+use pyo3::{exceptions::PyValueError, prelude::*, types::PyBytes};
+
+use crate::{BloomFilter, BloomFilterBuilder, BloomFlags, Transaction};
+
+fn value_error(err: impl std::fmt::Display) -> PyErr {
+    PyValueError::new_err(err.to_string())
+}
+
+/// Bloom filter, exported to Python as `BloomFilter`
+#[pyclass(name = "BloomFilter")]
+pub struct PyBloomFilter {
+    filter: BloomFilter,
+}
+
+#[pymethods]
+impl PyBloomFilter {
+    /// Create empty filter for `n_elements` with `false_positives_rate`.  Raises
+    /// `ValueError` if the parameters are invalid or exceed BIP-37 limits.
+    #[new]
+    #[pyo3(signature = (n_elements, false_positives_rate, n_tweak = 0, n_flags = 0))]
+    pub fn new(
+        n_elements: u32,
+        false_positives_rate: f64,
+        n_tweak: u32,
+        n_flags: u8,
+    ) -> PyResult<Self> {
+        let n_flags = BloomFlags::try_from(n_flags).map_err(value_error)?;
+        let filter = BloomFilterBuilder::new_n_tweak(n_elements, false_positives_rate, n_tweak)
+            .map_err(value_error)?
+            .build_with_n_flags(n_flags);
+        Ok(PyBloomFilter { filter })
+    }
+
+    /// Parse `filterload` payload
+    #[staticmethod]
+    pub fn from_filterload(bytes: &[u8]) -> PyResult<Self> {
+        let filter = BloomFilter::from_filterload_bytes(bytes).map_err(value_error)?;
+        Ok(PyBloomFilter { filter })
+    }
+
+    /// Parse hex encoded `filterload` payload as accepted by the RPC
+    #[staticmethod]
+    pub fn from_hex(s: &str) -> PyResult<Self> {
+        let filter = BloomFilter::from_rpc_hex(s).map_err(value_error)?;
+        Ok(PyBloomFilter { filter })
+    }
+
+    /// Insert data element, returns `False` if it was already covered by the filter
+    pub fn insert(&mut self, element: &[u8]) -> bool {
+        self.filter.insert(element)
+    }
+
+    /// Insert outpoint given by txid in internal byte order and output index
+    pub fn insert_outpoint(&mut self, txid: &[u8], vout: u32) -> PyResult<bool> {
+        let txid = txid
+            .try_into()
+            .map_err(|_| PyValueError::new_err("txid must be 32 bytes"))?;
+        Ok(self.filter.insert_outpoint(txid, vout))
+    }
+
+    /// Insert public key and its HASH160
+    pub fn insert_pubkey(&mut self, pubkey: &[u8]) -> bool {
+        self.filter.insert_pubkey(pubkey)
+    }
+
+    /// Check if the filter probably contains the element
+    pub fn contains(&self, element: &[u8]) -> bool {
+        self.filter.probably_contains(element)
+    }
+
+    fn __contains__(&self, element: &[u8]) -> bool {
+        self.contains(element)
+    }
+
+    /// Check if a raw transaction matches the filter.  Raises `ValueError` if the
+    /// transaction is malformed.
+    pub fn matches_transaction(&self, raw_tx: &[u8]) -> PyResult<bool> {
+        let tx = Transaction::from_bytes(raw_tx).map_err(value_error)?;
+        Ok(self.filter.matches_transaction(&tx))
+    }
+
+    /// Check if a raw transaction matches the filter, updating it according to `nFlags`
+    /// like a peer does.  Raises `ValueError` if the transaction is malformed.
+    pub fn matches_transaction_and_update(&mut self, raw_tx: &[u8]) -> PyResult<bool> {
+        let tx = Transaction::from_bytes(raw_tx).map_err(value_error)?;
+        Ok(self.filter.matches_transaction_and_update(&tx))
+    }
+
+    /// Serialize as `filterload` payload
+    pub fn to_filterload<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new_bound(py, &self.filter.to_filterload_bytes())
+    }
+
+    /// Serialize as hex encoded `filterload` payload
+    pub fn to_hex(&self) -> String {
+        self.filter.to_rpc_hex()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("BloomFilter.from_hex('{}')", self.filter.to_rpc_hex())
+    }
+}
+
+/// Python module `dashcore_bloom_filter`
+#[pymodule]
+fn dashcore_bloom_filter(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyBloomFilter>()
+}
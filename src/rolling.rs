@@ -0,0 +1,98 @@
+//! Rolling Bloom filter forgetting the oldest insertions.
+
+use crate::{hasher::Hasher, BadFilterParameters, BloomFilterBuilder, MAX_HASH_FUNCS};
+
+/// Bloom filter which keeps roughly the last `n_elements` insertions with the requested
+/// false positives rate, same as Dash Core's `CRollingBloomFilter`.
+///
+/// Insertions are split into three generations of `n_elements / 2` items each, once the
+/// current generation is full the oldest one is wiped out.  Every bit is represented by
+/// a pair of bits storing the generation number it was last set in.
+#[derive(Debug, Clone)]
+pub struct RollingBloomFilter {
+    entries_per_generation: u32,
+    entries_this_generation: u32,
+    generation: u32,
+    data: Vec<u64>,
+    hasher: Hasher,
+}
+
+impl RollingBloomFilter {
+    /// Create new rolling Bloom filter remembering at least `n_elements` last insertions.
+    /// [BadFilterParameters] returned if there are no elements or the false positives
+    /// rate is not within `(0, 1)`.
+    pub fn new(
+        n_elements: u32,
+        false_positives_rate: f64,
+        n_tweak: u32,
+    ) -> Result<Self, BadFilterParameters> {
+        BloomFilterBuilder::check_inputs(n_elements, false_positives_rate)?;
+
+        let log_fp_rate = false_positives_rate.ln();
+        let n_hash_funcs = ((log_fp_rate / 0.5_f64.ln()).round() as u32).clamp(1, MAX_HASH_FUNCS);
+        let entries_per_generation = n_elements.div_ceil(2);
+        let max_elements = entries_per_generation as f64 * 3.0;
+        let filter_bits = (-(n_hash_funcs as f64) * max_elements
+            / (1.0 - (log_fp_rate / n_hash_funcs as f64).exp()).ln())
+        .ceil() as u64;
+        let data_len = (filter_bits.div_ceil(64) * 2) as usize;
+
+        Ok(RollingBloomFilter {
+            entries_per_generation,
+            entries_this_generation: 0,
+            generation: 1,
+            data: vec![0; data_len],
+            hasher: Hasher::new(n_hash_funcs, n_tweak, 0),
+        })
+    }
+
+    /// Add element to the filter, possibly forgetting the oldest generation of elements
+    pub fn insert(&mut self, element: &[u8]) {
+        if self.entries_this_generation == self.entries_per_generation {
+            self.entries_this_generation = 0;
+            self.generation += 1;
+            if self.generation == 4 {
+                self.generation = 1;
+            }
+
+            let generation_mask1 = 0u64.wrapping_sub((self.generation & 1).into());
+            let generation_mask2 = 0u64.wrapping_sub((self.generation >> 1).into());
+            for pair in self.data.chunks_exact_mut(2) {
+                let mask = (pair[0] ^ generation_mask1) | (pair[1] ^ generation_mask2);
+                pair[0] &= mask;
+                pair[1] &= mask;
+            }
+        }
+        self.entries_this_generation += 1;
+
+        let generation_bit1 = u64::from(self.generation & 1);
+        let generation_bit2 = u64::from(self.generation >> 1);
+        for hash in self.hasher.hashes(element) {
+            let (pos, bit) = self.position(hash);
+            self.data[pos] = (self.data[pos] & !(1 << bit)) | generation_bit1 << bit;
+            self.data[pos | 1] = (self.data[pos | 1] & !(1 << bit)) | generation_bit2 << bit;
+        }
+    }
+
+    /// Check if the filter possibly contains the element
+    pub fn probably_contains(&self, element: &[u8]) -> bool {
+        self.hasher.hashes(element).all(|hash| {
+            let (pos, bit) = self.position(hash);
+            (self.data[pos] | self.data[pos | 1]) >> bit & 1 == 1
+        })
+    }
+
+    /// Forget all elements
+    pub fn reset(&mut self) {
+        self.entries_this_generation = 0;
+        self.generation = 1;
+        self.data.fill(0);
+    }
+
+    /// Index of the first word of the pair and the bit position within it
+    fn position(&self, hash: u32) -> (usize, u32) {
+        let bit = hash & 0x3F;
+        let pos = ((u64::from(hash) * self.data.len() as u64) >> 32) as usize;
+        (pos & !1, bit)
+    }
+}
@@ -0,0 +1,171 @@
+//! Scalable Bloom filter growing with the number of elements.
+
+use std::collections::HashSet;
+
+use crate::{BadFilterParameters, BloomFilter, BloomFilterBuilder, BloomFlags};
+
+/// Ratio by which the false positives rate of every next layer is tightened
+const TIGHTENING_RATIO: f64 = 0.5;
+
+/// Factor by which the capacity of every next layer grows
+const GROWTH_FACTOR: u32 = 2;
+
+/// Bloom filter which doesn't require the number of elements to be known upfront.
+///
+/// Elements are added to the last of a chain of filters, once it is full a new one with
+/// doubled capacity and halved false positives rate is appended, so the compound false
+/// positives rate stays below the requested one.  Inserted elements are retained to be
+/// able to rebuild a single BIP-37 filter with [Self::to_bloom_filter].
+#[derive(Debug, Clone)]
+pub struct ScalableBloomFilter {
+    false_positives_rate: f64,
+    n_tweak: u32,
+    layers: Vec<Layer>,
+    elements: HashSet<Vec<u8>>,
+}
+
+#[derive(Debug, Clone)]
+struct Layer {
+    filter: BloomFilter,
+    capacity: u32,
+    len: u32,
+}
+
+impl ScalableBloomFilter {
+    /// Create new scalable Bloom filter starting with `initial_capacity` elements
+    /// capacity and satisfying the false positives rate
+    pub fn new(
+        initial_capacity: u32,
+        false_positives_rate: f64,
+        n_tweak: u32,
+    ) -> Result<Self, BadFilterParameters> {
+        Ok(ScalableBloomFilter {
+            false_positives_rate,
+            n_tweak,
+            layers: vec![Self::first_layer(
+                initial_capacity,
+                false_positives_rate,
+                n_tweak,
+            )?],
+            elements: HashSet::new(),
+        })
+    }
+
+    fn first_layer(
+        capacity: u32,
+        false_positives_rate: f64,
+        n_tweak: u32,
+    ) -> Result<Layer, BadFilterParameters> {
+        Self::layer(
+            capacity,
+            false_positives_rate * (1.0 - TIGHTENING_RATIO),
+            n_tweak,
+        )
+    }
+
+    fn layer(
+        capacity: u32,
+        false_positives_rate: f64,
+        n_tweak: u32,
+    ) -> Result<Layer, BadFilterParameters> {
+        let filter =
+            BloomFilterBuilder::new_n_tweak(capacity, false_positives_rate, n_tweak)?.build();
+        Ok(Layer {
+            filter,
+            capacity,
+            len: 0,
+        })
+    }
+
+    /// Add element to the filter, appending a new layer if the current one is full.
+    /// Returns `false` if the element was already inserted.  [BadFilterParameters]
+    /// returned if a new layer would exceed BIP-37 limits.
+    pub fn insert(&mut self, element: &[u8]) -> Result<bool, BadFilterParameters> {
+        if self.elements.contains(element) {
+            return Ok(false);
+        }
+
+        let last = self.layers.last().expect("at least one layer");
+        if last.len >= last.capacity {
+            let layer = Self::layer(
+                last.capacity.saturating_mul(GROWTH_FACTOR),
+                self.false_positives_rate
+                    * (1.0 - TIGHTENING_RATIO)
+                    * TIGHTENING_RATIO.powi(self.layers.len() as i32),
+                self.n_tweak,
+            )?;
+            self.layers.push(layer);
+        }
+
+        let last = self.layers.last_mut().expect("at least one layer");
+        last.filter.insert(element);
+        last.len += 1;
+        self.elements.insert(element.to_vec());
+
+        Ok(true)
+    }
+
+    /// Check if the filter possibly contains the element
+    pub fn probably_contains(&self, element: &[u8]) -> bool {
+        self.layers
+            .iter()
+            .any(|layer| layer.filter.probably_contains(element))
+    }
+
+    /// Number of inserted elements
+    pub fn len(&self) -> usize {
+        self.elements.len()
+    }
+
+    /// Check if no elements were inserted
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty()
+    }
+
+    /// Number of layers
+    pub fn n_layers(&self) -> usize {
+        self.layers.len()
+    }
+
+    /// Rebuild all inserted elements into a single BIP-37 filter sized for their number
+    /// with the false positives rate of this filter
+    pub fn to_bloom_filter(&self, n_flags: BloomFlags) -> Result<BloomFilter, BadFilterParameters> {
+        let n_elements = u32::try_from(self.elements.len().max(1)).unwrap_or(u32::MAX);
+        Ok(
+            BloomFilterBuilder::new_n_tweak(n_elements, self.false_positives_rate, self.n_tweak)?
+                .add_elements(&self.elements)
+                .build_with_n_flags(n_flags),
+        )
+    }
+}
+
+/// Wipes the retained elements, `nTweak` and the layers, as they reveal what the wallet
+/// is watching.  The layers are replaced with an empty first layer of the initial
+/// capacity, so new elements can still be inserted.
+#[cfg(feature = "zeroize")]
+impl zeroize::Zeroize for ScalableBloomFilter {
+    fn zeroize(&mut self) {
+        self.n_tweak.zeroize();
+        for layer in &mut self.layers {
+            layer.filter.zeroize();
+        }
+        for mut element in std::mem::take(&mut self.elements) {
+            element.zeroize();
+        }
+        let capacity = self.layers[0].capacity;
+        self.layers = vec![
+            Self::first_layer(capacity, self.false_positives_rate, self.n_tweak)
+                .expect("parameters were checked on creation"),
+        ];
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl Drop for ScalableBloomFilter {
+    fn drop(&mut self) {
+        zeroize::Zeroize::zeroize(self);
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl zeroize::ZeroizeOnDrop for ScalableBloomFilter {}
@@ -0,0 +1,133 @@
+//! Matching a stream of transactions against a Bloom filter with explanations.
+
+use std::collections::HashSet;
+
+use crate::{script, BloomFilter, OutPoint, Transaction};
+
+/// Why a transaction was found relevant
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MatchReason {
+    /// Transaction id is in the filter
+    Txid,
+    /// Data element of an output script is in the filter
+    OutputElement {
+        /// Index of the output
+        vout: u32,
+        /// Matched data element
+        element: Vec<u8>,
+    },
+    /// Input spends an outpoint that is in the filter or was discovered earlier
+    SpentOutpoint(OutPoint),
+    /// Data element of an input script is in the filter
+    InputElement {
+        /// Index of the input
+        index: u32,
+        /// Matched data element
+        element: Vec<u8>,
+    },
+}
+
+/// Transaction found relevant by [RelevanceScanner]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransactionMatch {
+    /// Transaction id in internal byte order
+    pub txid: [u8; 32],
+    /// All reasons the transaction matched
+    pub reasons: Vec<MatchReason>,
+}
+
+/// Scanner of mempool or block transactions reporting which of them are relevant to a
+/// Bloom filter and why.
+///
+/// Outpoints of matched outputs are remembered, so transactions spending them are
+/// reported even if the filter itself wasn't updated.
+#[derive(Debug, Clone)]
+pub struct RelevanceScanner {
+    filter: BloomFilter,
+    outpoints: HashSet<OutPoint>,
+}
+
+impl RelevanceScanner {
+    /// Create new scanner matching transactions against the filter
+    pub fn new(filter: BloomFilter) -> Self {
+        RelevanceScanner {
+            filter,
+            outpoints: HashSet::new(),
+        }
+    }
+
+    /// Check the transaction, returning all reasons it matched or `None` if it is not
+    /// relevant
+    pub fn scan(&mut self, tx: &Transaction) -> Option<TransactionMatch> {
+        let mut reasons = Vec::new();
+
+        if self.filter.probably_contains(&tx.txid) {
+            reasons.push(MatchReason::Txid);
+        }
+
+        for (vout, output) in tx.outputs.iter().enumerate() {
+            let vout = vout as u32;
+            let matched = self.matched_elements(&output.script_pubkey);
+            if !matched.is_empty() {
+                self.outpoints.insert(OutPoint {
+                    txid: tx.txid,
+                    vout,
+                });
+            }
+            reasons.extend(
+                matched
+                    .into_iter()
+                    .map(|element| MatchReason::OutputElement { vout, element }),
+            );
+        }
+
+        for (index, input) in tx.inputs.iter().enumerate() {
+            let outpoint = input.previous_output;
+            if self.outpoints.contains(&outpoint)
+                || self
+                    .filter
+                    .probably_contains_outpoint(&outpoint.txid, outpoint.vout)
+            {
+                reasons.push(MatchReason::SpentOutpoint(outpoint));
+            }
+            reasons.extend(
+                self.matched_elements(&input.script_sig)
+                    .into_iter()
+                    .map(|element| MatchReason::InputElement {
+                        index: index as u32,
+                        element,
+                    }),
+            );
+        }
+
+        (!reasons.is_empty()).then_some(TransactionMatch {
+            txid: tx.txid,
+            reasons,
+        })
+    }
+
+    /// Check transactions in order, returning matches of the relevant ones
+    pub fn scan_all<'a, I>(&mut self, txs: I) -> Vec<TransactionMatch>
+    where
+        I: IntoIterator<Item = &'a Transaction>,
+    {
+        txs.into_iter().filter_map(|tx| self.scan(tx)).collect()
+    }
+
+    fn matched_elements(&self, script: &[u8]) -> Vec<Vec<u8>> {
+        script::push_data(script)
+            .filter(|data| !data.is_empty() && self.filter.probably_contains(data))
+            .map(<[u8]>::to_vec)
+            .collect()
+    }
+
+    /// Get outpoints of matched outputs discovered so far
+    pub fn discovered_outpoints(&self) -> &HashSet<OutPoint> {
+        &self.outpoints
+    }
+
+    /// Get the filter transactions are matched against
+    pub fn filter(&self) -> &BloomFilter {
+        &self.filter
+    }
+}
@@ -0,0 +1,89 @@
+//! Script data elements extraction.
+//!
+//! BIP-37 matches scripts by their data elements, which are the values pushed by push
+//! opcodes, so no script interpretation is needed to feed or query a filter.
+
+const OP_PUSHDATA1: u8 = 0x4C;
+const OP_PUSHDATA2: u8 = 0x4D;
+const OP_PUSHDATA4: u8 = 0x4E;
+
+/// Iterate over data pushed by the script's push opcodes, skipping non-push opcodes.
+/// `OP_0` yields an empty element.
+/// Iteration stops on a push which runs past the end of the script.
+pub fn push_data(script: &[u8]) -> impl Iterator<Item = &[u8]> {
+    let mut rest = script;
+    std::iter::from_fn(move || loop {
+        let (&opcode, tail) = rest.split_first()?;
+        rest = tail;
+
+        let len = match opcode {
+            0x00..=0x4B => opcode as usize,
+            OP_PUSHDATA1 => read_len::<1>(&mut rest)?,
+            OP_PUSHDATA2 => read_len::<2>(&mut rest)?,
+            OP_PUSHDATA4 => read_len::<4>(&mut rest)?,
+            _ => continue,
+        };
+
+        if len > rest.len() {
+            rest = &[];
+            return None;
+        }
+        let (data, tail) = rest.split_at(len);
+        rest = tail;
+        return Some(data);
+    })
+}
+
+fn read_len<const N: usize>(rest: &mut &[u8]) -> Option<usize> {
+    if rest.len() < N {
+        *rest = &[];
+        return None;
+    }
+    let (len_bytes, tail) = rest.split_at(N);
+    *rest = tail;
+
+    let mut buf = [0; 4];
+    buf[..N].copy_from_slice(len_bytes);
+    Some(u32::from_le_bytes(buf) as usize)
+}
+
+const OP_1: u8 = 0x51;
+const OP_16: u8 = 0x60;
+const OP_CHECKSIG: u8 = 0xAC;
+const OP_CHECKMULTISIG: u8 = 0xAE;
+
+fn is_pubkey(data: &[u8]) -> bool {
+    matches!(data.len(), 33 | 65)
+}
+
+/// Check if the script is a pay-to-pubkey output script: `<pubkey> OP_CHECKSIG`.
+pub fn is_p2pk(script: &[u8]) -> bool {
+    match script {
+        [len, pubkey @ .., OP_CHECKSIG] => *len as usize == pubkey.len() && is_pubkey(pubkey),
+        _ => false,
+    }
+}
+
+/// Check if the script is a bare multisig output script:
+/// `OP_m <pubkey>... OP_n OP_CHECKMULTISIG`.
+pub fn is_multisig(script: &[u8]) -> bool {
+    let [m @ OP_1..=OP_16, pubkeys @ .., n @ OP_1..=OP_16, OP_CHECKMULTISIG] = script else {
+        return false;
+    };
+    if m > n {
+        return false;
+    }
+
+    let mut n_pubkeys = 0;
+    let mut rest = pubkeys;
+    while let [len, tail @ ..] = rest {
+        let len = *len as usize;
+        if len > tail.len() || !is_pubkey(&tail[..len]) {
+            return false;
+        }
+        rest = &tail[len..];
+        n_pubkeys += 1;
+    }
+
+    n_pubkeys == (n - OP_1 + 1) as usize
+}
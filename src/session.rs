@@ -0,0 +1,167 @@
+//! Filter session producing BIP-37 messages for a changing set of watched elements.
+
+use std::collections::BTreeSet;
+
+use crate::{BadFilterParameters, BloomFilter, BloomFilterBuilder, BloomFlags, MAX_FILTERADD_SIZE};
+
+/// Factor by which the filter capacity exceeds the number of watched elements on rebuild
+const GROWTH_FACTOR: u32 = 2;
+
+/// BIP-37 P2P message to be sent to peers to keep their copy of the filter up to date
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FilterMessage {
+    /// `filterload` payload replacing the filter
+    FilterLoad(Vec<u8>),
+    /// `filteradd` payload adding an element to the filter
+    FilterAdd(Vec<u8>),
+    /// `filterclear` removing the filter, it has no payload
+    FilterClear,
+}
+
+/// Bloom filter over a set of watched elements which tells what messages should be sent
+/// to peers as the set changes.
+///
+/// Added elements are announced with `filteradd` while the filter has capacity left,
+/// otherwise and on removals the filter is rebuilt and sent with `filterload`.  Once the
+/// last element is removed the filter is dropped with `filterclear`.
+///
+/// Whether peers need an update is told by the filter's dirty state, see
+/// [BloomFilter::is_dirty], and it is marked clean once the message is returned.
+#[derive(Debug, Clone)]
+pub struct FilterSession {
+    false_positives_rate: f64,
+    n_tweak: u32,
+    n_flags: BloomFlags,
+    elements: BTreeSet<Vec<u8>>,
+    filter: Option<BloomFilter>,
+    capacity: u32,
+}
+
+impl FilterSession {
+    /// Create new session with no watched elements and no filter loaded.
+    /// [BadFilterParameters] returned if the false positives rate is not within `(0, 1)`.
+    pub fn new(
+        false_positives_rate: f64,
+        n_tweak: u32,
+        n_flags: BloomFlags,
+    ) -> Result<Self, BadFilterParameters> {
+        BloomFilterBuilder::check_inputs(1, false_positives_rate)?;
+
+        Ok(FilterSession {
+            false_positives_rate,
+            n_tweak,
+            n_flags,
+            elements: BTreeSet::new(),
+            filter: None,
+            capacity: 0,
+        })
+    }
+
+    /// Start watching the element.  Returns the message to send, or `None` if the
+    /// element was already watched or covered by the loaded filter.
+    /// [BadFilterParameters] returned if a rebuilt filter would exceed BIP-37 limits.
+    pub fn add(&mut self, element: &[u8]) -> Result<Option<FilterMessage>, BadFilterParameters> {
+        if !self.elements.insert(element.to_vec()) {
+            return Ok(None);
+        }
+
+        match &mut self.filter {
+            Some(filter)
+                if self.elements.len() <= self.capacity as usize
+                    && element.len() <= MAX_FILTERADD_SIZE =>
+            {
+                filter.insert(element);
+                if !filter.is_dirty() {
+                    return Ok(None);
+                }
+                let payload = filter.drain_filteradd_payloads().pop();
+                filter.mark_clean();
+                Ok(payload.map(FilterMessage::FilterAdd))
+            }
+            _ => self.reload().map(Some),
+        }
+    }
+
+    /// Stop watching the element.  Returns the message to send, or `None` if the element
+    /// wasn't watched.  [BadFilterParameters] returned if a rebuilt filter would exceed
+    /// BIP-37 limits.
+    pub fn remove(&mut self, element: &[u8]) -> Result<Option<FilterMessage>, BadFilterParameters> {
+        if !self.elements.remove(element) {
+            return Ok(None);
+        }
+        self.reload().map(Some)
+    }
+
+    /// Rebuild the filter with another `nTweak`, so peers can't correlate the new filter
+    /// with the previous one.  Returns `None` if there are no watched elements.
+    pub fn rotate(&mut self, n_tweak: u32) -> Result<Option<FilterMessage>, BadFilterParameters> {
+        self.n_tweak = n_tweak;
+        if self.elements.is_empty() {
+            return Ok(None);
+        }
+        self.reload().map(Some)
+    }
+
+    fn reload(&mut self) -> Result<FilterMessage, BadFilterParameters> {
+        if self.elements.is_empty() {
+            self.filter = None;
+            self.capacity = 0;
+            return Ok(FilterMessage::FilterClear);
+        }
+
+        let n_elements = u32::try_from(self.elements.len()).unwrap_or(u32::MAX);
+        let capacity = n_elements.saturating_mul(GROWTH_FACTOR);
+        let mut filter =
+            BloomFilterBuilder::new_n_tweak(capacity, self.false_positives_rate, self.n_tweak)?
+                .add_elements(&self.elements)
+                .build_with_n_flags(self.n_flags);
+        filter.enable_filteradd_tracking();
+        let message = FilterMessage::FilterLoad(filter.to_filterload_bytes());
+        filter.mark_clean();
+
+        self.filter = Some(filter);
+        self.capacity = capacity;
+        Ok(message)
+    }
+
+    /// Get the filter peers are expected to have loaded
+    pub fn filter(&self) -> Option<&BloomFilter> {
+        self.filter.as_ref()
+    }
+
+    /// Number of watched elements
+    pub fn len(&self) -> usize {
+        self.elements.len()
+    }
+
+    /// Check if no elements are watched
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty()
+    }
+}
+
+/// Wipes the watched elements, `nTweak` and the filter.  The session is left with no
+/// filter loaded, so the next added element is sent with `filterload`.
+#[cfg(feature = "zeroize")]
+impl zeroize::Zeroize for FilterSession {
+    fn zeroize(&mut self) {
+        self.n_tweak.zeroize();
+        for mut element in std::mem::take(&mut self.elements) {
+            element.zeroize();
+        }
+        if let Some(mut filter) = self.filter.take() {
+            filter.zeroize();
+        }
+        self.capacity = 0;
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl Drop for FilterSession {
+    fn drop(&mut self) {
+        zeroize::Zeroize::zeroize(self);
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl zeroize::ZeroizeOnDrop for FilterSession {}
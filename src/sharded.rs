@@ -0,0 +1,80 @@
+//! Bloom filter split into shards to spread concurrent insertions.
+
+use std::num::NonZeroUsize;
+
+use crate::{hasher, AtomicBloomFilter, BadFilterParameters, BloomFilter, BloomFlags};
+
+/// Seed of the hash selecting a shard, distinct from BIP-37 seeds for small `nTweak`
+const SHARD_SEED: u32 = 0x5348_5244;
+
+/// Bloom filter made of independent [AtomicBloomFilter] shards, every element is stored
+/// in the shard selected by its hash.  Threads inserting different elements mostly
+/// write to different bit arrays, so they don't contend for the same cache lines.
+///
+/// All shards are sized for the total number of elements with the same parameters, so
+/// OR-ing them gives the filter a single [BloomFilter] holding all elements would be,
+/// see [Self::to_bloom_filter].  This costs `n_shards` times the memory of that filter,
+/// up to `n_shards` times [MAX_BLOOM_FILTER_SIZE](crate::MAX_BLOOM_FILTER_SIZE) bytes,
+/// see [Self::size_bytes].  The exported filter has the same false positives rate as
+/// the single one.
+#[derive(Debug)]
+pub struct ShardedBloomFilter {
+    shards: Vec<AtomicBloomFilter>,
+}
+
+impl ShardedBloomFilter {
+    /// Create new sharded Bloom filter with `n_shards` shards, each sized like at
+    /// [BloomFilterBuilder::new_n_tweak](crate::BloomFilterBuilder::new_n_tweak)
+    pub fn new(
+        n_elements: u32,
+        false_positives_rate: f64,
+        n_tweak: u32,
+        n_shards: NonZeroUsize,
+    ) -> Result<Self, BadFilterParameters> {
+        let shard = AtomicBloomFilter::new(n_elements, false_positives_rate, n_tweak)?;
+        let empty = shard.to_bloom_filter(BloomFlags::UpdateNone);
+        let mut shards = vec![shard];
+        shards.extend((1..n_shards.get()).map(|_| AtomicBloomFilter::from(empty.clone())));
+        Ok(ShardedBloomFilter { shards })
+    }
+
+    fn shard(&self, element: &[u8]) -> &AtomicBloomFilter {
+        let hash = hasher::murmur3_32(element, SHARD_SEED);
+        &self.shards[hash as usize % self.shards.len()]
+    }
+
+    /// Add element to its shard, returns `false` if it was already covered by the shard
+    pub fn insert(&self, element: &[u8]) -> bool {
+        self.shard(element).insert(element)
+    }
+
+    /// Check if the filter possibly contains the element.  Only the element's shard is
+    /// checked, so false positives are rarer than in the exported filter.
+    pub fn probably_contains(&self, element: &[u8]) -> bool {
+        self.shard(element).probably_contains(element)
+    }
+
+    /// Get number of shards
+    pub fn n_shards(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Get total size of all shards in bytes
+    pub fn size_bytes(&self) -> usize {
+        self.shards.iter().map(AtomicBloomFilter::size_bytes).sum()
+    }
+
+    /// Export a snapshot of all shards merged into a single BIP-37 Bloom filter
+    pub fn to_bloom_filter(&self, n_flags: BloomFlags) -> BloomFilter {
+        let mut shards = self
+            .shards
+            .iter()
+            .map(|shard| shard.to_bloom_filter(n_flags));
+        let first = shards.next().expect("there is at least one shard");
+        shards.fold(first, |merged, shard| {
+            merged
+                .union(&shard)
+                .expect("shards are built with the same parameters")
+        })
+    }
+}
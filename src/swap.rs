@@ -0,0 +1,51 @@
+//! Bloom filter replaced atomically while readers keep querying.
+
+use std::sync::Arc;
+
+use arc_swap::ArcSwap;
+
+use crate::BloomFilter;
+
+/// Bloom filter shared between readers and a writer which rebuilds or rotates it in the
+/// background and then swaps it in atomically.  Readers never block: every query sees
+/// either the previous or the new filter as a whole, and snapshots taken with
+/// [Self::load] stay valid after a swap.
+#[derive(Debug)]
+pub struct SwappableBloomFilter {
+    current: ArcSwap<BloomFilter>,
+}
+
+impl SwappableBloomFilter {
+    /// Share the filter
+    pub fn new(filter: BloomFilter) -> Self {
+        SwappableBloomFilter {
+            current: ArcSwap::from_pointee(filter),
+        }
+    }
+
+    /// Get a snapshot of the current filter, unaffected by later swaps
+    pub fn load(&self) -> Arc<BloomFilter> {
+        self.current.load_full()
+    }
+
+    /// Check if the current filter possibly contains the item
+    pub fn probably_contains(&self, item: &[u8]) -> bool {
+        self.current.load().probably_contains(item)
+    }
+
+    /// Replace the filter, returning the previous one.  Readers holding snapshots keep
+    /// using the previous filter until they drop them.
+    pub fn store(&self, filter: BloomFilter) -> Arc<BloomFilter> {
+        self.current.swap(Arc::new(filter))
+    }
+
+    /// Replace the filter with one derived from the current, e.g. with elements added.
+    /// If another writer swaps a filter in meanwhile, `update` is called again with it,
+    /// so no update is lost.
+    pub fn update<F>(&self, mut update: F)
+    where
+        F: FnMut(&BloomFilter) -> BloomFilter,
+    {
+        self.current.rcu(|current| update(current));
+    }
+}
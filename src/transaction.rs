@@ -0,0 +1,101 @@
+//! Minimal transaction representation used for BIP-37 matching.
+
+use crate::{hashes, wire};
+
+/// Error type to indicate that bytes are not a serialized Dash transaction.
+#[derive(Debug, thiserror::Error, PartialEq)]
+#[error("malformed transaction")]
+pub struct InvalidTransaction;
+
+/// Reference to a transaction output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct OutPoint {
+    /// Transaction id in internal byte order
+    pub txid: [u8; 32],
+    /// Output index
+    pub vout: u32,
+}
+
+/// Transaction input
+#[derive(Debug, Clone)]
+pub struct TxIn {
+    /// Output spent by this input
+    pub previous_output: OutPoint,
+    /// Input script
+    pub script_sig: Vec<u8>,
+}
+
+/// Transaction output
+#[derive(Debug, Clone)]
+pub struct TxOut {
+    /// Output script
+    pub script_pubkey: Vec<u8>,
+}
+
+/// Transaction fields relevant to BIP-37 filtering
+#[derive(Debug, Clone)]
+pub struct Transaction {
+    /// Transaction id in internal byte order
+    pub txid: [u8; 32],
+    /// Transaction inputs
+    pub inputs: Vec<TxIn>,
+    /// Transaction outputs
+    pub outputs: Vec<TxOut>,
+}
+
+impl Transaction {
+    /// Parse a raw Dash transaction, computing its txid.  DIP-2 special transactions
+    /// are supported, their extra payload is skipped.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, InvalidTransaction> {
+        Self::read(&mut wire::Reader::new(bytes), bytes).ok_or(InvalidTransaction)
+    }
+
+    fn read(reader: &mut wire::Reader, bytes: &[u8]) -> Option<Self> {
+        let version = reader.read_u16_le()?;
+        let tx_type = reader.read_u16_le()?;
+
+        let n_inputs = reader.read_compact_size()?;
+        let inputs = (0..n_inputs)
+            .map(|_| {
+                let txid = reader.read_bytes(32)?.try_into().expect("length checked");
+                let vout = reader.read_u32_le()?;
+                let script_sig = Self::read_script(reader)?;
+                reader.read_u32_le()?;
+                Some(TxIn {
+                    previous_output: OutPoint { txid, vout },
+                    script_sig,
+                })
+            })
+            .collect::<Option<_>>()?;
+
+        let n_outputs = reader.read_compact_size()?;
+        let outputs = (0..n_outputs)
+            .map(|_| {
+                reader.read_u64_le()?;
+                let script_pubkey = Self::read_script(reader)?;
+                Some(TxOut { script_pubkey })
+            })
+            .collect::<Option<_>>()?;
+
+        reader.read_u32_le()?;
+        if version >= 3 && tx_type != 0 {
+            Self::read_script(reader)?;
+        }
+        if !reader.is_empty() {
+            return None;
+        }
+
+        Some(Transaction {
+            txid: hashes::sha256d(bytes),
+            inputs,
+            outputs,
+        })
+    }
+
+    fn read_script(reader: &mut wire::Reader) -> Option<Vec<u8>> {
+        let len = reader.read_compact_size()?;
+        reader
+            .read_bytes(usize::try_from(len).ok()?)
+            .map(<[u8]>::to_vec)
+    }
+}
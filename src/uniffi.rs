@@ -0,0 +1,161 @@
+//! Kotlin and Swift bindings for mobile wallets generated with `uniffi`.
+//!
+//! Objects are shared through `Arc` by the generated code, so they are synchronized
+//! internally.  The shared library is built with
+//! `cargo rustc --release --features uniffi --crate-type cdylib`.
+
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::{BloomFilter, BloomFilterBuilder, BloomFlags};
+
+/// Error thrown by the bindings
+#[derive(Debug, thiserror::Error, uniffi::Error)]
+#[uniffi(flat_error)]
+pub enum UniffiFilterError {
+    /// Filter parameters are invalid or exceed BIP-37 limits
+    #[error(transparent)]
+    BadParameters(#[from] crate::BadFilterParameters),
+    /// `nFlags` is not `0`, `1` or `2`
+    #[error(transparent)]
+    UnknownFlags(#[from] crate::UnknownBloomFlags),
+    /// Serialized filter is malformed or exceeds BIP-37 limits
+    #[error(transparent)]
+    InvalidData(#[from] crate::InvalidFilterData),
+    /// Address is not a Dash P2PKH or P2SH address
+    #[cfg(feature = "addresses")]
+    #[error(transparent)]
+    InvalidAddress(#[from] crate::InvalidAddress),
+    /// Txid is not 32 bytes
+    #[error("txid must be 32 bytes")]
+    InvalidTxid,
+    /// Builder was used after [UniffiBloomFilterBuilder::build]
+    #[error("builder was already built")]
+    AlreadyBuilt,
+}
+
+/// Builder of a Bloom filter
+#[derive(uniffi::Object)]
+pub struct UniffiBloomFilterBuilder {
+    builder: Mutex<Option<BloomFilterBuilder>>,
+}
+
+#[uniffi::export]
+impl UniffiBloomFilterBuilder {
+    /// Create new builder for a filter of `n_elements` with `false_positives_rate`
+    #[uniffi::constructor]
+    pub fn new(
+        n_elements: u32,
+        false_positives_rate: f64,
+        n_tweak: u32,
+    ) -> Result<Arc<Self>, UniffiFilterError> {
+        let builder = BloomFilterBuilder::new_n_tweak(n_elements, false_positives_rate, n_tweak)?;
+        Ok(Arc::new(UniffiBloomFilterBuilder {
+            builder: Mutex::new(Some(builder)),
+        }))
+    }
+
+    /// Add data element to the filter
+    pub fn add_element(&self, element: Vec<u8>) -> Result<(), UniffiFilterError> {
+        self.update(|builder| Ok(builder.add_element(&element)))
+    }
+
+    /// Add public key and its HASH160 to the filter
+    pub fn add_pubkey(&self, pubkey: Vec<u8>) -> Result<(), UniffiFilterError> {
+        self.update(|builder| Ok(builder.add_pubkey(&pubkey)))
+    }
+
+    /// Add outpoint given by txid in internal byte order and output index
+    pub fn add_outpoint(&self, txid: Vec<u8>, vout: u32) -> Result<(), UniffiFilterError> {
+        let txid: [u8; 32] = txid
+            .try_into()
+            .map_err(|_| UniffiFilterError::InvalidTxid)?;
+        self.update(|builder| Ok(builder.add_outpoint(&txid, vout)))
+    }
+
+    /// Build the filter with `nFlags` of `0`, `1` or `2`.  The builder can't be used
+    /// afterwards.
+    pub fn build(&self, n_flags: u8) -> Result<Arc<UniffiBloomFilter>, UniffiFilterError> {
+        let n_flags = BloomFlags::try_from(n_flags)?;
+        let builder = lock(&self.builder)
+            .take()
+            .ok_or(UniffiFilterError::AlreadyBuilt)?;
+        Ok(UniffiBloomFilter::wrap(builder.build_with_n_flags(n_flags)))
+    }
+}
+
+#[cfg(feature = "addresses")]
+#[uniffi::export]
+impl UniffiBloomFilterBuilder {
+    /// Add Dash P2PKH or P2SH address to the filter
+    pub fn add_address(&self, address: String) -> Result<(), UniffiFilterError> {
+        self.update(|builder| Ok(builder.add_address(&address)?))
+    }
+}
+
+impl UniffiBloomFilterBuilder {
+    fn update(
+        &self,
+        f: impl FnOnce(BloomFilterBuilder) -> Result<BloomFilterBuilder, UniffiFilterError>,
+    ) -> Result<(), UniffiFilterError> {
+        let mut builder = lock(&self.builder);
+        let taken = builder.take().ok_or(UniffiFilterError::AlreadyBuilt)?;
+        *builder = Some(f(taken)?);
+        Ok(())
+    }
+}
+
+/// Bloom filter
+#[derive(uniffi::Object)]
+pub struct UniffiBloomFilter {
+    filter: Mutex<BloomFilter>,
+}
+
+#[uniffi::export]
+impl UniffiBloomFilter {
+    /// Parse `filterload` payload
+    #[uniffi::constructor]
+    pub fn from_filterload(bytes: Vec<u8>) -> Result<Arc<Self>, UniffiFilterError> {
+        Ok(Self::wrap(BloomFilter::from_filterload_bytes(&bytes)?))
+    }
+
+    /// Parse hex encoded `filterload` payload as accepted by the RPC
+    #[uniffi::constructor]
+    pub fn from_hex(s: String) -> Result<Arc<Self>, UniffiFilterError> {
+        Ok(Self::wrap(BloomFilter::from_rpc_hex(&s)?))
+    }
+
+    /// Insert data element, returns `false` if it was already covered by the filter
+    pub fn insert(&self, element: Vec<u8>) -> bool {
+        lock(&self.filter).insert(&element)
+    }
+
+    /// Check if the filter probably contains the element
+    pub fn contains(&self, element: Vec<u8>) -> bool {
+        lock(&self.filter).probably_contains(&element)
+    }
+
+    /// Serialize as `filterload` payload
+    pub fn to_filterload(&self) -> Vec<u8> {
+        lock(&self.filter).to_filterload_bytes()
+    }
+
+    /// Serialize as hex encoded `filterload` payload
+    pub fn to_hex(&self) -> String {
+        lock(&self.filter).to_rpc_hex()
+    }
+}
+
+impl UniffiBloomFilter {
+    fn wrap(filter: BloomFilter) -> Arc<Self> {
+        Arc::new(UniffiBloomFilter {
+            filter: Mutex::new(filter),
+        })
+    }
+}
+
+/// Lock ignoring poisoning, the guarded values stay consistent if a caller panics
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
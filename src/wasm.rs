@@ -0,0 +1,129 @@
+//! JavaScript bindings for browser wallets built with `wasm-bindgen`.
+//!
+//! The module is built with `cargo rustc --release --target wasm32-unknown-unknown
+//! --features wasm --crate-type cdylib` and processed with the `wasm-bindgen` CLI.
+
+use wasm_bindgen::prelude::*;
+
+use crate::{BloomFilter, BloomFilterBuilder, BloomFlags};
+
+/// Builder of a Bloom filter, exported to JavaScript as `BloomFilterBuilder`
+#[wasm_bindgen(js_name = BloomFilterBuilder)]
+pub struct WasmBloomFilterBuilder {
+    builder: Option<BloomFilterBuilder>,
+}
+
+#[wasm_bindgen(js_class = BloomFilterBuilder)]
+impl WasmBloomFilterBuilder {
+    /// Create new builder for a filter of `n_elements` with `false_positives_rate`.
+    /// Throws if the parameters are invalid or exceed BIP-37 limits.
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        n_elements: u32,
+        false_positives_rate: f64,
+        n_tweak: u32,
+    ) -> Result<WasmBloomFilterBuilder, JsError> {
+        let builder = BloomFilterBuilder::new_n_tweak(n_elements, false_positives_rate, n_tweak)?;
+        Ok(WasmBloomFilterBuilder {
+            builder: Some(builder),
+        })
+    }
+
+    /// Add data element to the filter
+    #[wasm_bindgen(js_name = addElement)]
+    pub fn add_element(&mut self, element: &[u8]) -> Result<(), JsError> {
+        let builder = self.take()?;
+        self.builder = Some(builder.add_element(element));
+        Ok(())
+    }
+
+    /// Add public key and its HASH160 to the filter
+    #[wasm_bindgen(js_name = addPubkey)]
+    pub fn add_pubkey(&mut self, pubkey: &[u8]) -> Result<(), JsError> {
+        let builder = self.take()?;
+        self.builder = Some(builder.add_pubkey(pubkey));
+        Ok(())
+    }
+
+    /// Add outpoint given by txid in internal byte order and output index
+    #[wasm_bindgen(js_name = addOutpoint)]
+    pub fn add_outpoint(&mut self, txid: &[u8], vout: u32) -> Result<(), JsError> {
+        let txid = txid
+            .try_into()
+            .map_err(|_| JsError::new("txid must be 32 bytes"))?;
+        let builder = self.take()?;
+        self.builder = Some(builder.add_outpoint(txid, vout));
+        Ok(())
+    }
+
+    /// Add Dash P2PKH or P2SH address to the filter
+    #[cfg(feature = "addresses")]
+    #[wasm_bindgen(js_name = addAddress)]
+    pub fn add_address(&mut self, address: &str) -> Result<(), JsError> {
+        let builder = self.take()?;
+        self.builder = Some(builder.add_address(address)?);
+        Ok(())
+    }
+
+    /// Build the filter with `nFlags` of `0`, `1` or `2`.  The builder can't be used
+    /// afterwards.
+    pub fn build(&mut self, n_flags: u8) -> Result<WasmBloomFilter, JsError> {
+        let n_flags = BloomFlags::try_from(n_flags)?;
+        Ok(WasmBloomFilter {
+            filter: self.take()?.build_with_n_flags(n_flags),
+        })
+    }
+
+    fn take(&mut self) -> Result<BloomFilterBuilder, JsError> {
+        self.builder
+            .take()
+            .ok_or_else(|| JsError::new("builder was already built"))
+    }
+}
+
+/// Bloom filter, exported to JavaScript as `BloomFilter`
+#[wasm_bindgen(js_name = BloomFilter)]
+pub struct WasmBloomFilter {
+    filter: BloomFilter,
+}
+
+#[wasm_bindgen(js_class = BloomFilter)]
+impl WasmBloomFilter {
+    /// Parse `filterload` payload.  Throws if it is malformed or exceeds BIP-37 limits.
+    #[wasm_bindgen(js_name = fromFilterload)]
+    pub fn from_filterload(bytes: &[u8]) -> Result<WasmBloomFilter, JsError> {
+        Ok(WasmBloomFilter {
+            filter: BloomFilter::from_filterload_bytes(bytes)?,
+        })
+    }
+
+    /// Parse hex encoded `filterload` payload as accepted by the RPC
+    #[wasm_bindgen(js_name = fromHex)]
+    pub fn from_hex(s: &str) -> Result<WasmBloomFilter, JsError> {
+        Ok(WasmBloomFilter {
+            filter: BloomFilter::from_rpc_hex(s)?,
+        })
+    }
+
+    /// Insert data element, returns `false` if it was already covered by the filter
+    pub fn insert(&mut self, element: &[u8]) -> bool {
+        self.filter.insert(element)
+    }
+
+    /// Check if the filter probably contains the element
+    pub fn contains(&self, element: &[u8]) -> bool {
+        self.filter.probably_contains(element)
+    }
+
+    /// Serialize as `filterload` payload
+    #[wasm_bindgen(js_name = toFilterload)]
+    pub fn to_filterload(&self) -> Vec<u8> {
+        self.filter.to_filterload_bytes()
+    }
+
+    /// Serialize as hex encoded `filterload` payload
+    #[wasm_bindgen(js_name = toHex)]
+    pub fn to_hex(&self) -> String {
+        self.filter.to_rpc_hex()
+    }
+}
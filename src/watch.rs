@@ -0,0 +1,217 @@
+//! Bloom filter over a wallet's watch items rebuilt as the wallet grows.
+
+use std::collections::HashSet;
+
+use crate::{hashes, BadFilterParameters, BloomFilter, BloomFilterBuilder, BloomFlags, OutPoint};
+
+/// Factor by which the filter capacity exceeds the number of elements on rebuild
+const GROWTH_FACTOR: u32 = 2;
+
+/// Smallest number of elements the filter is sized for
+const MIN_CAPACITY: u32 = 16;
+
+/// Item a wallet wants to be notified about
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum WatchItem {
+    /// Public key hash or script hash, matches P2PKH and P2SH outputs
+    Hash160([u8; 20]),
+    /// Public key, matches P2PK, P2PKH and multisig outputs and inputs spending them
+    Pubkey(Vec<u8>),
+    /// Transaction outpoint, matches transactions spending it
+    Outpoint(OutPoint),
+    /// Arbitrary data element
+    Element(Vec<u8>),
+}
+
+impl WatchItem {
+    /// Create watch item for the HASH160 of a public key, without the key itself
+    pub fn pubkey_hash(pubkey: &[u8]) -> Self {
+        WatchItem::Hash160(hashes::hash160(pubkey))
+    }
+
+    /// Create watch item for a Dash P2PKH or P2SH address
+    #[cfg(feature = "addresses")]
+    pub fn from_address(address: &str) -> Result<Self, crate::InvalidAddress> {
+        crate::address::decode_hash160(address).map(WatchItem::Hash160)
+    }
+
+    /// Number of filter elements the item takes
+    fn n_elements(&self) -> u32 {
+        match self {
+            WatchItem::Pubkey(_) => 2,
+            _ => 1,
+        }
+    }
+
+    fn insert_into(&self, filter: &mut BloomFilter) {
+        match self {
+            WatchItem::Hash160(hash) => filter.insert_pubkey_hash(hash),
+            WatchItem::Pubkey(pubkey) => filter.insert_pubkey(pubkey),
+            WatchItem::Outpoint(outpoint) => filter.insert_outpoint(&outpoint.txid, outpoint.vout),
+            WatchItem::Element(element) => filter.insert(element),
+        };
+    }
+}
+
+/// Bloom filter owning a set of watch items, which is rebuilt with a larger size once the
+/// number of elements makes its false positives rate exceed the target.
+///
+/// Every rebuild produces a filter peers haven't seen, so it has to be sent with
+/// `filterload`, see [Self::take_rebuilt].  Between rebuilds peers can be updated with
+/// `filteradd`, see [BloomFilter::drain_filteradd_payloads], which is enabled for the
+/// filter.
+#[derive(Debug, Clone)]
+pub struct WatchFilter {
+    false_positives_rate: f64,
+    n_tweak: u32,
+    n_flags: BloomFlags,
+    items: HashSet<WatchItem>,
+    n_elements: u32,
+    filter: BloomFilter,
+    rebuilt: bool,
+}
+
+impl WatchFilter {
+    /// Create new watch filter with no items keeping the false positives rate below
+    /// `false_positives_rate`.  [BadFilterParameters] returned if the false positives
+    /// rate is not within `(0, 1)`.
+    pub fn new(
+        false_positives_rate: f64,
+        n_tweak: u32,
+        n_flags: BloomFlags,
+    ) -> Result<Self, BadFilterParameters> {
+        Ok(WatchFilter {
+            false_positives_rate,
+            n_tweak,
+            n_flags,
+            items: HashSet::new(),
+            n_elements: 0,
+            filter: Self::initial_filter(false_positives_rate, n_tweak, n_flags)?,
+            rebuilt: true,
+        })
+    }
+
+    fn initial_filter(
+        false_positives_rate: f64,
+        n_tweak: u32,
+        n_flags: BloomFlags,
+    ) -> Result<BloomFilter, BadFilterParameters> {
+        let mut filter =
+            BloomFilterBuilder::new_n_tweak(MIN_CAPACITY, false_positives_rate, n_tweak)?
+                .build_with_n_flags(n_flags);
+        filter.enable_filteradd_tracking();
+        Ok(filter)
+    }
+
+    /// Add the item to the filter, rebuilding it if the false positives rate would exceed
+    /// the target.  Returns `false` if the item was already watched.
+    /// [BadFilterParameters] returned if a rebuilt filter would exceed BIP-37 limits.
+    pub fn watch(&mut self, item: WatchItem) -> Result<bool, BadFilterParameters> {
+        if self.items.contains(&item) {
+            return Ok(false);
+        }
+
+        let n_elements = self.n_elements.saturating_add(item.n_elements());
+        if self.filter.effective_fp_rate(n_elements) > self.false_positives_rate {
+            self.rebuild(n_elements, &item)?;
+        } else {
+            item.insert_into(&mut self.filter);
+        }
+
+        self.n_elements = n_elements;
+        self.items.insert(item);
+        Ok(true)
+    }
+
+    fn rebuild(&mut self, n_elements: u32, item: &WatchItem) -> Result<(), BadFilterParameters> {
+        let capacity = n_elements.saturating_mul(GROWTH_FACTOR).max(MIN_CAPACITY);
+        let mut filter =
+            BloomFilterBuilder::new_n_tweak(capacity, self.false_positives_rate, self.n_tweak)?
+                .build_with_n_flags(self.n_flags);
+        for watched in self.items.iter().chain([item]) {
+            watched.insert_into(&mut filter);
+        }
+        filter.enable_filteradd_tracking();
+
+        self.filter = filter;
+        self.rebuilt = true;
+        Ok(())
+    }
+
+    /// Check if the filter was rebuilt since [Self::take_rebuilt] was called, so peers
+    /// need it sent with `filterload`.  The initial filter counts as rebuilt.
+    pub fn is_rebuilt(&self) -> bool {
+        self.rebuilt
+    }
+
+    /// Get `filterload` payload of the filter if it was rebuilt since the previous call
+    pub fn take_rebuilt(&mut self) -> Option<Vec<u8>> {
+        if !std::mem::take(&mut self.rebuilt) {
+            return None;
+        }
+        self.filter.mark_clean();
+        Some(self.filter.to_filterload_bytes())
+    }
+
+    /// Get the filter
+    pub fn filter(&self) -> &BloomFilter {
+        &self.filter
+    }
+
+    /// Get the filter to match transactions with updates or to drain `filteradd`
+    /// payloads
+    pub fn filter_mut(&mut self) -> &mut BloomFilter {
+        &mut self.filter
+    }
+
+    /// Number of watched items
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Check if no items are watched
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl zeroize::Zeroize for WatchItem {
+    fn zeroize(&mut self) {
+        match self {
+            WatchItem::Hash160(hash) => hash.zeroize(),
+            WatchItem::Pubkey(data) | WatchItem::Element(data) => data.zeroize(),
+            WatchItem::Outpoint(outpoint) => {
+                outpoint.txid.zeroize();
+                outpoint.vout.zeroize();
+            }
+        }
+    }
+}
+
+/// Wipes the watch items, `nTweak` and the filter.  The filter is replaced with an empty
+/// initial one, which counts as rebuilt, so peers get it with `filterload`.
+#[cfg(feature = "zeroize")]
+impl zeroize::Zeroize for WatchFilter {
+    fn zeroize(&mut self) {
+        self.n_tweak.zeroize();
+        self.n_elements.zeroize();
+        for mut item in std::mem::take(&mut self.items) {
+            item.zeroize();
+        }
+        self.filter.zeroize();
+        self.filter = Self::initial_filter(self.false_positives_rate, self.n_tweak, self.n_flags)
+            .expect("false positives rate was checked on creation");
+        self.rebuilt = true;
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl Drop for WatchFilter {
+    fn drop(&mut self) {
+        zeroize::Zeroize::zeroize(self);
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl zeroize::ZeroizeOnDrop for WatchFilter {}
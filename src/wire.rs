@@ -0,0 +1,78 @@
+//! Dash P2P wire format primitives.
+
+/// Append a `CompactSize` unsigned integer (Bitcoin/Dash varint) to the buffer.
+pub(crate) fn write_compact_size(buf: &mut Vec<u8>, n: u64) {
+    match n {
+        0..=0xFC => buf.push(n as u8),
+        0xFD..=0xFFFF => {
+            buf.push(0xFD);
+            buf.extend_from_slice(&(n as u16).to_le_bytes());
+        }
+        0x10000..=0xFFFF_FFFF => {
+            buf.push(0xFE);
+            buf.extend_from_slice(&(n as u32).to_le_bytes());
+        }
+        _ => {
+            buf.push(0xFF);
+            buf.extend_from_slice(&n.to_le_bytes());
+        }
+    }
+}
+
+/// Cursor over a byte slice for decoding wire format data, `None` is returned on
+/// malformed or truncated input.
+pub(crate) struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        Reader { data }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub(crate) fn read_bytes(&mut self, n: usize) -> Option<&'a [u8]> {
+        if n > self.data.len() {
+            return None;
+        }
+        let (head, tail) = self.data.split_at(n);
+        self.data = tail;
+        Some(head)
+    }
+
+    pub(crate) fn read_remaining(&mut self) -> &'a [u8] {
+        std::mem::take(&mut self.data)
+    }
+
+    pub(crate) fn read_u8(&mut self) -> Option<u8> {
+        self.read_bytes(1).map(|b| b[0])
+    }
+
+    pub(crate) fn read_u16_le(&mut self) -> Option<u16> {
+        self.read_bytes(2)
+            .map(|b| u16::from_le_bytes(b.try_into().expect("length checked")))
+    }
+
+    pub(crate) fn read_u32_le(&mut self) -> Option<u32> {
+        self.read_bytes(4)
+            .map(|b| u32::from_le_bytes(b.try_into().expect("length checked")))
+    }
+
+    pub(crate) fn read_u64_le(&mut self) -> Option<u64> {
+        self.read_bytes(8)
+            .map(|b| u64::from_le_bytes(b.try_into().expect("length checked")))
+    }
+
+    /// Read a `CompactSize` unsigned integer rejecting non-canonical encodings.
+    pub(crate) fn read_compact_size(&mut self) -> Option<u64> {
+        match self.read_u8()? {
+            0xFD => self.read_u16_le().map(u64::from).filter(|n| *n >= 0xFD),
+            0xFE => self.read_u32_le().map(u64::from).filter(|n| *n >= 0x10000),
+            0xFF => self.read_u64_le().filter(|n| *n >= 0x1_0000_0000),
+            n => Some(n.into()),
+        }
+    }
+}
@@ -0,0 +1,142 @@
+//! Static XOR filter.
+
+use std::hash::Hasher as _;
+
+use siphasher::sip::SipHasher24;
+
+/// Immutable filter built from a final set of elements, using about 9.84 bits per
+/// element for a false positives rate of about `1 / 256`.
+///
+/// Built with the 3-wise XOR filter construction: every element maps to three slots
+/// whose 8-bit fingerprints XOR to the element's fingerprint.
+#[derive(Debug, Clone)]
+pub struct XorFilter {
+    seed: u64,
+    block_length: u32,
+    fingerprints: Vec<u8>,
+    len: usize,
+}
+
+impl XorFilter {
+    /// Build the filter from elements, duplicates are allowed
+    pub fn new<I>(elements: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: AsRef<[u8]>,
+    {
+        let elements: Vec<I::Item> = elements.into_iter().collect();
+        let capacity = 32 + (1.23 * elements.len() as f64).ceil() as u32;
+        let block_length = capacity / 3;
+
+        let mut seed = 0;
+        loop {
+            let mut filter = XorFilter {
+                seed,
+                block_length,
+                fingerprints: vec![0; block_length as usize * 3],
+                len: 0,
+            };
+
+            let mut hashes: Vec<u64> = elements.iter().map(|e| filter.hash(e.as_ref())).collect();
+            hashes.sort_unstable();
+            hashes.dedup();
+            filter.len = hashes.len();
+
+            if filter.assign(&hashes) {
+                return filter;
+            }
+            seed += 1;
+        }
+    }
+
+    fn hash(&self, element: &[u8]) -> u64 {
+        let mut hasher = SipHasher24::new_with_keys(self.seed, 0);
+        hasher.write(element);
+        hasher.finish()
+    }
+
+    fn slots(&self, hash: u64) -> [usize; 3] {
+        let reduce = |h: u64| ((u64::from(h as u32) * u64::from(self.block_length)) >> 32) as usize;
+        let block_length = self.block_length as usize;
+        [
+            reduce(hash),
+            reduce(hash.rotate_left(21)) + block_length,
+            reduce(hash.rotate_left(42)) + 2 * block_length,
+        ]
+    }
+
+    fn fingerprint(hash: u64) -> u8 {
+        (hash ^ (hash >> 32)) as u8
+    }
+
+    /// Peel the hypergraph of slots and assign fingerprints, `false` returned if the
+    /// hypergraph has a cycle and another seed should be tried
+    fn assign(&mut self, hashes: &[u64]) -> bool {
+        let n_slots = self.fingerprints.len();
+        let mut counts = vec![0u32; n_slots];
+        let mut xors = vec![0u64; n_slots];
+        for hash in hashes {
+            for slot in self.slots(*hash) {
+                counts[slot] += 1;
+                xors[slot] ^= hash;
+            }
+        }
+
+        let mut queue: Vec<usize> = (0..n_slots).filter(|i| counts[*i] == 1).collect();
+        let mut stack = Vec::with_capacity(hashes.len());
+        while let Some(slot) = queue.pop() {
+            if counts[slot] != 1 {
+                continue;
+            }
+            let hash = xors[slot];
+            stack.push((hash, slot));
+            for other in self.slots(hash) {
+                counts[other] -= 1;
+                xors[other] ^= hash;
+                if counts[other] == 1 {
+                    queue.push(other);
+                }
+            }
+        }
+
+        if stack.len() != hashes.len() {
+            return false;
+        }
+
+        for (hash, slot) in stack.into_iter().rev() {
+            let [a, b, c] = self.slots(hash);
+            self.fingerprints[slot] = 0;
+            self.fingerprints[slot] = Self::fingerprint(hash)
+                ^ self.fingerprints[a]
+                ^ self.fingerprints[b]
+                ^ self.fingerprints[c];
+        }
+        true
+    }
+
+    /// Check if the filter possibly contains the element
+    pub fn probably_contains(&self, element: &[u8]) -> bool {
+        if self.len == 0 {
+            return false;
+        }
+        let hash = self.hash(element);
+        let [a, b, c] = self.slots(hash);
+        Self::fingerprint(hash)
+            == self.fingerprints[a] ^ self.fingerprints[b] ^ self.fingerprints[c]
+    }
+
+    /// Number of distinct elements the filter was built from
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Check if the filter was built from no elements
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Size of the filter in bytes
+    pub fn size_bytes(&self) -> usize {
+        self.fingerprints.len()
+    }
+}
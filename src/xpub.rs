@@ -0,0 +1,23 @@
+//! HD wallet public key derivation.
+
+use bip32::{ChildNumber, XPub};
+
+/// Error type to indicate an extended public key that cannot be used to populate a Bloom
+/// filter.
+#[derive(Debug, thiserror::Error)]
+#[error("invalid extended public key: {0}")]
+pub struct InvalidXpub(#[from] bip32::Error);
+
+/// Derive compressed public keys of the first `gap` addresses of the receive (`0`) or
+/// change (`1`) chain.
+pub(crate) fn derive_chain(
+    xpub: &XPub,
+    chain: u32,
+    gap: u32,
+) -> Result<impl Iterator<Item = Result<[u8; 33], InvalidXpub>> + '_, InvalidXpub> {
+    let chain_xpub = xpub.derive_child(ChildNumber::new(chain, false)?)?;
+    Ok((0..gap).map(move |i| {
+        let child = chain_xpub.derive_child(ChildNumber::new(i, false)?)?;
+        Ok(child.to_bytes())
+    }))
+}